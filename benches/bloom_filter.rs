@@ -0,0 +1,101 @@
+use bloom_filter::{BlockedBloomFilter, BloomFilter, BloomFilterArgs};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn args() -> BloomFilterArgs {
+    BloomFilterArgs::builder()
+        .expected_items(100_000)
+        .false_positive_rate(0.01)
+        .build()
+}
+
+fn keys(prefix: &str, count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("{prefix}-{i}")).collect()
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let keys = keys("insert", 1_000);
+    let mut bloom_filter: BloomFilter<String> = BloomFilter::with(args());
+    c.bench_function("insert", |b| {
+        b.iter(|| {
+            for key in &keys {
+                bloom_filter.insert(black_box(key));
+            }
+        })
+    });
+}
+
+fn bench_contains(c: &mut Criterion) {
+    let present = keys("present", 1_000);
+    let absent = keys("absent", 1_000);
+    let mut bloom_filter: BloomFilter<String> = BloomFilter::with(args());
+    present.iter().for_each(|key| bloom_filter.insert(key));
+
+    c.bench_function("contains/present", |b| {
+        b.iter(|| {
+            for key in &present {
+                black_box(bloom_filter.contains(black_box(key)));
+            }
+        })
+    });
+    c.bench_function("contains/absent", |b| {
+        b.iter(|| {
+            for key in &absent {
+                black_box(bloom_filter.contains(black_box(key)));
+            }
+        })
+    });
+}
+
+/// Compares probing a filter much larger than the CPU caches, where every
+/// probe of the plain filter is a likely cache miss.
+fn bench_blocked(c: &mut Criterion) {
+    let args = BloomFilterArgs::builder()
+        .expected_items(20_000_000)
+        .false_positive_rate(0.01)
+        .build();
+    let present = keys("present", 100_000);
+    let absent = keys("absent", 100_000);
+    let mut plain: BloomFilter<String> = BloomFilter::with(args);
+    let mut blocked: BlockedBloomFilter<String> = BlockedBloomFilter::with(args);
+    for key in &present {
+        plain.insert(key);
+        blocked.insert(key);
+    }
+
+    let mut group = c.benchmark_group("large");
+    group.bench_function("bloom_filter/present", |b| {
+        b.iter(|| {
+            for key in &present {
+                black_box(plain.contains(black_box(key)));
+            }
+        })
+    });
+    group.bench_function("blocked_bloom_filter/present", |b| {
+        b.iter(|| {
+            for key in &present {
+                black_box(blocked.contains(black_box(key)));
+            }
+        })
+    });
+    group.bench_function("bloom_filter/present_batched", |b| {
+        b.iter(|| black_box(plain.contains_many(black_box(&present))))
+    });
+    group.bench_function("bloom_filter/absent", |b| {
+        b.iter(|| {
+            for key in &absent {
+                black_box(plain.contains(black_box(key)));
+            }
+        })
+    });
+    group.bench_function("blocked_bloom_filter/absent", |b| {
+        b.iter(|| {
+            for key in &absent {
+                black_box(blocked.contains(black_box(key)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert, bench_contains, bench_blocked);
+criterion_main!(benches);
@@ -0,0 +1,206 @@
+use crate::{
+    hash::HashIndices, BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher,
+    Bytes, Hashed, KeyEncoding, Xxh3,
+};
+use bit_vec::BitVec;
+use std::{
+    borrow::Borrow,
+    hash::Hash,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// A bloom filter that can be shared between threads without a lock. Bits
+/// are stored in `AtomicU64` words and set with `fetch_or`, so concurrent
+/// inserts never lose each other's bits.
+///
+/// A value inserted by one thread is guaranteed to be visible to another
+/// thread once the two have synchronized some other way (for example by
+/// joining the inserting thread); a concurrent `contains` may see only some
+/// of its bits and answer "no".
+pub struct AtomicBloomFilter<T, E = Bytes, S = Xxh3> {
+    words: Box<[AtomicU64]>,
+    bits: usize,
+    hashes: usize,
+    hasher: S,
+    // The filter never stores a T, so it is Send and Sync whatever T is.
+    _phantom: PhantomData<fn() -> (T, E)>,
+}
+
+impl<T: AsRef<[u8]>> AtomicBloomFilter<T> {
+    /// Creates a new AtomicBloomFilter with the default arguments.
+    pub fn new() -> Self {
+        AtomicBloomFilter::with(BloomFilterArgs::default())
+    }
+
+    /// Creates a new AtomicBloomFilter with the given arguments.
+    pub fn with(args: BloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for AtomicBloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> AtomicBloomFilter<T, Hashed> {
+    /// Creates a new AtomicBloomFilter over `Hash` values with the default
+    /// arguments.
+    pub fn new_hashed() -> Self {
+        AtomicBloomFilter::with_hashed(BloomFilterArgs::default())
+    }
+
+    /// Creates a new AtomicBloomFilter over `Hash` values with the given
+    /// arguments.
+    pub fn with_hashed(args: BloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> AtomicBloomFilter<T, E, S> {
+    /// Creates a new AtomicBloomFilter with the given arguments and hash
+    /// strategy.
+    pub fn with_hasher(args: BloomFilterArgs, hasher: S) -> Self {
+        Self {
+            words: (0..args.bits.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            bits: args.bits,
+            hashes: args.hashes,
+            hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The number of bits (m) in the AtomicBloomFilter.
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// The number of hash functions (k) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Inserts a new value into the AtomicBloomFilter.
+    pub fn insert<Q>(&self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        for idx in self.calculate_hash_indices(value) {
+            self.words[idx / 64].fetch_or(1 << (idx % 64), Ordering::Relaxed);
+        }
+    }
+
+    /// Checks if the AtomicBloomFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        for idx in self.calculate_hash_indices(value) {
+            if !self.get(idx) {
+                return BloomFilterContainsResponse::No;
+            }
+        }
+        BloomFilterContainsResponse::Maybe
+    }
+
+    /// Copies the current bits into a plain BloomFilter, for example to
+    /// serialize it.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, E, S>
+    where
+        S: Clone,
+    {
+        BloomFilter {
+            bits: BitVec::from_fn(self.bits, |idx| self.get(idx)),
+            hashes: self.hashes,
+            hasher: self.hasher.clone(),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self.words[idx / 64].load(Ordering::Relaxed) & (1 << (idx % 64)) != 0
+    }
+
+    fn calculate_hash_indices<Q>(&self, value: &Q) -> HashIndices
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        HashIndices::new(E::hash_pair(value, &self.hasher), self.hashes, self.bits)
+    }
+}
+
+impl<T, E, S: BloomHasher> From<BloomFilter<T, E, S>> for AtomicBloomFilter<T, E, S> {
+    fn from(bloom_filter: BloomFilter<T, E, S>) -> Self {
+        let mut words = vec![0u64; bloom_filter.bits.len().div_ceil(64)];
+        for idx in bloom_filter.bits.iter().enumerate().filter(|(_, bit)| *bit).map(|(idx, _)| idx) {
+            words[idx / 64] |= 1 << (idx % 64);
+        }
+        Self {
+            words: words.into_iter().map(AtomicU64::new).collect(),
+            bits: bloom_filter.bits.len(),
+            hashes: bloom_filter.hashes,
+            hasher: bloom_filter.hasher,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicBloomFilter;
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, Hashed};
+    use std::{rc::Rc, thread};
+
+    #[test]
+    fn atomic_bloom_filter_is_send_and_sync() {
+        fn assert_send_sync<F: Send + Sync>() {}
+        assert_send_sync::<AtomicBloomFilter<String>>();
+        assert_send_sync::<AtomicBloomFilter<Rc<str>, Hashed>>();
+    }
+
+    #[test]
+    fn atomic_bloom_filter_loses_no_concurrent_inserts() {
+        const THREADS: u64 = 8;
+        const PER_THREAD: u64 = 10_000;
+        let args = BloomFilterArgs::builder()
+            .expected_items((THREADS * PER_THREAD) as usize)
+            .false_positive_rate(0.01)
+            .build();
+        let atomic: AtomicBloomFilter<u64, Hashed> = AtomicBloomFilter::with_hashed(args);
+
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let atomic = &atomic;
+                scope.spawn(move || {
+                    (thread * PER_THREAD..(thread + 1) * PER_THREAD).for_each(|i| atomic.insert(&i));
+                });
+            }
+        });
+
+        let mut sequential: BloomFilter<u64, Hashed> = BloomFilter::with_hashed(args);
+        (0..THREADS * PER_THREAD).for_each(|i| sequential.insert(&i));
+        assert!((0..THREADS * PER_THREAD)
+            .all(|i| atomic.contains(&i) == BloomFilterContainsResponse::Maybe));
+        assert_eq!(atomic.to_bloom_filter().to_bytes(), sequential.to_bytes());
+    }
+
+    #[test]
+    fn atomic_bloom_filter_converts_from_bloom_filter() {
+        let mut bloom_filter: BloomFilter<String> =
+            BloomFilter::with(BloomFilterArgs::builder().bits(100).build());
+        bloom_filter.insert("shared");
+        let bytes = bloom_filter.to_bytes();
+
+        let atomic = AtomicBloomFilter::from(bloom_filter);
+        assert_eq!(atomic.contains("shared"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(atomic.contains("other"), BloomFilterContainsResponse::No);
+        assert_eq!(atomic.to_bloom_filter().to_bytes(), bytes);
+    }
+}
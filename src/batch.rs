@@ -0,0 +1,254 @@
+use crate::{
+    hash::HashIndices, BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher,
+    KeyEncoding,
+};
+use std::{borrow::Borrow, iter::FromIterator};
+
+/// How many values are hashed and prefetched ahead of being probed.
+const BATCH: usize = 16;
+
+impl<T, E, S: BloomHasher> BloomFilter<T, E, S> {
+    /// Inserts every value of the iterator into the BloomFilter.
+    pub fn insert_all<'a, I, Q>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized + 'a,
+    {
+        let (words, bits) = (self.bits.storage().as_ptr(), self.bits.len());
+        for_each_prefetched(
+            values,
+            |value| HashIndices::new(E::hash_pair(value, &self.hasher), self.hashes, bits),
+            words,
+            |indices| {
+                indices.for_each(|idx| self.bits.set(idx, true));
+                true
+            },
+        );
+    }
+
+    /// Checks every value of the iterator, returning one response per value.
+    pub fn contains_many<'a, I, Q>(&self, values: I) -> Vec<BloomFilterContainsResponse>
+    where
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized + 'a,
+    {
+        let values = values.into_iter();
+        let mut responses = Vec::with_capacity(values.size_hint().0);
+        self.probe_all(values, |response| {
+            responses.push(response);
+            true
+        });
+        responses
+    }
+
+    /// Returns "maybe" if the BloomFilter may contain every value of the
+    /// iterator, and "no" as soon as one value is definitely missing.
+    pub fn contains_all<'a, I, Q>(&self, values: I) -> BloomFilterContainsResponse
+    where
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized + 'a,
+    {
+        let mut result = BloomFilterContainsResponse::Maybe;
+        self.probe_all(values, |response| {
+            result = response;
+            result == BloomFilterContainsResponse::Maybe
+        });
+        result
+    }
+
+    /// Returns "maybe" as soon as the BloomFilter may contain a value of the
+    /// iterator, and "no" if every value is definitely missing.
+    pub fn contains_any<'a, I, Q>(&self, values: I) -> BloomFilterContainsResponse
+    where
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized + 'a,
+    {
+        let mut result = BloomFilterContainsResponse::No;
+        self.probe_all(values, |response| {
+            result = response;
+            result == BloomFilterContainsResponse::No
+        });
+        result
+    }
+
+    /// Answers each value in turn until `respond` returns false.
+    fn probe_all<'a, I, Q>(&self, values: I, mut respond: impl FnMut(BloomFilterContainsResponse) -> bool)
+    where
+        I: IntoIterator<Item = &'a Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized + 'a,
+    {
+        for_each_prefetched(
+            values,
+            |value| self.calculate_hash_indices(value),
+            self.bits.storage().as_ptr(),
+            |mut indices| {
+                let found = indices.all(|idx| self.bits.get(idx).unwrap_or(false));
+                respond(if found {
+                    BloomFilterContainsResponse::Maybe
+                } else {
+                    BloomFilterContainsResponse::No
+                })
+            },
+        );
+    }
+}
+
+/// Hashes values in batches, prefetching the storage words each batch will
+/// touch before handing its indices to `probe`, so the memory accesses of
+/// a whole batch overlap instead of stalling one after another. Stops as
+/// soon as `probe` returns false.
+fn for_each_prefetched<V, I>(
+    values: I,
+    mut indices_of: impl FnMut(V) -> HashIndices,
+    words: *const u32,
+    mut probe: impl FnMut(HashIndices) -> bool,
+) where
+    I: IntoIterator<Item = V>,
+{
+    let mut values = values.into_iter();
+    let mut batch = [HashIndices::new((0, 0), 0, 1); BATCH];
+    loop {
+        let mut len = 0;
+        for value in values.by_ref().take(BATCH) {
+            let indices = indices_of(value);
+            indices.for_each(|idx| prefetch(words.wrapping_add(idx / 32)));
+            batch[len] = indices;
+            len += 1;
+        }
+        if len == 0 {
+            return;
+        }
+        for &indices in &batch[..len] {
+            if !probe(indices) {
+                return;
+            }
+        }
+    }
+}
+
+/// Hints the CPU to load the cache line at `ptr`. Never dereferences it.
+#[inline(always)]
+fn prefetch(ptr: *const u32) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: prefetching is only a hint and never faults, and SSE is part
+    // of the x86_64 baseline.
+    unsafe {
+        std::arch::x86_64::_mm_prefetch::<{ std::arch::x86_64::_MM_HINT_T0 }>(ptr as *const i8)
+    };
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = ptr;
+}
+
+impl<T, E: KeyEncoding<T>, S: BloomHasher> Extend<T> for BloomFilter<T, E, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        for value in values {
+            self.insert(&value);
+        }
+    }
+}
+
+/// Collects values into a BloomFilter sized for the number of values at a
+/// 1% false-positive rate, or with the default arguments if there are none.
+/// Iterators that don't report an exact length are buffered first, so the
+/// filter is never sized from a lower bound that is too small.
+impl<T, E: KeyEncoding<T>, S: BloomHasher + Default> FromIterator<T> for BloomFilter<T, E, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+        let values = values.into_iter();
+        match values.size_hint() {
+            (lower, Some(upper)) if lower == upper => Self::sized_for(lower, values),
+            _ => {
+                let values: Vec<T> = values.collect();
+                Self::sized_for(values.len(), values)
+            }
+        }
+    }
+}
+
+impl<T, E: KeyEncoding<T>, S: BloomHasher + Default> BloomFilter<T, E, S> {
+    fn sized_for(len: usize, values: impl IntoIterator<Item = T>) -> Self {
+        let args = match len {
+            0 => BloomFilterArgs::default(),
+            expected_items => BloomFilterArgs::builder()
+                .expected_items(expected_items)
+                .false_positive_rate(0.01)
+                .build(),
+        };
+        let mut bloom_filter = Self::with_hasher(args, S::default());
+        bloom_filter.extend(values);
+        bloom_filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, Hashed};
+
+    #[test]
+    fn bloom_filter_batch_insert_matches_single_inserts() {
+        let keys: Vec<String> = (0..1_000).map(|i| format!("key-{}", i)).collect();
+        let args = BloomFilterArgs::builder().expected_items(1_000).false_positive_rate(0.01).build();
+        let mut batched: BloomFilter<String> = BloomFilter::with(args);
+        let mut single: BloomFilter<String> = BloomFilter::with(args);
+        batched.insert_all(&keys);
+        keys.iter().for_each(|key| single.insert(key));
+        assert_eq!(batched.to_bytes(), single.to_bytes());
+
+        let mut borrowed: BloomFilter<String> = BloomFilter::with(args);
+        borrowed.insert_all(keys.iter().map(String::as_str));
+        assert_eq!(borrowed.to_bytes(), single.to_bytes());
+    }
+
+    #[test]
+    fn bloom_filter_batch_contains() {
+        let mut bloom_filter: BloomFilter<u32, Hashed> = BloomFilter::new_hashed();
+        bloom_filter.insert_all(&[1, 2, 3]);
+
+        let responses = bloom_filter.contains_many(&[1, 2, 3, 1_000]);
+        assert_eq!(responses.len(), 4);
+        assert!(responses[..3].iter().all(|r| *r == BloomFilterContainsResponse::Maybe));
+        assert_eq!(responses[3], bloom_filter.contains(&1_000));
+
+        let many: Vec<u32> = (0..100).collect();
+        let expected = many.iter().map(|i| bloom_filter.contains(i)).collect::<Vec<_>>();
+        assert_eq!(bloom_filter.contains_many(&many), expected);
+
+        assert_eq!(bloom_filter.contains_all(&[1, 2, 3]), BloomFilterContainsResponse::Maybe);
+        assert_eq!(bloom_filter.contains_all(&[1, 2, 4_000]), BloomFilterContainsResponse::No);
+        assert_eq!(bloom_filter.contains_all(&[]), BloomFilterContainsResponse::Maybe);
+        assert_eq!(bloom_filter.contains_any(&[4_000, 2]), BloomFilterContainsResponse::Maybe);
+        assert_eq!(bloom_filter.contains_any(&[4_000, 5_000]), BloomFilterContainsResponse::No);
+        assert_eq!(bloom_filter.contains_any(&[]), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn bloom_filter_extend_and_collect() {
+        let keys: Vec<String> = (0..10_000).map(|i| i.to_string()).collect();
+        let collected: BloomFilter<String> = keys.iter().cloned().collect();
+        assert!(collected.bits() > 90_000);
+        assert_eq!(collected.contains_all(&keys), BloomFilterContainsResponse::Maybe);
+
+        let mut extended: BloomFilter<String> = BloomFilter::new();
+        extended.extend(vec![String::from("a"), String::from("b")]);
+        assert_eq!(extended.contains_all(["a", "b"]), BloomFilterContainsResponse::Maybe);
+    }
+
+    #[test]
+    fn bloom_filter_collect_sizes_from_inexact_iterators() {
+        // `filter` reports a lower bound of zero, which must not leave the
+        // filter at the default size.
+        let collected: BloomFilter<String> =
+            (0..100_000).filter(|_| true).map(|i| i.to_string()).collect();
+        assert!(collected.bits() > 900_000);
+        assert!(collected.fill_ratio() < 0.6, "fill ratio {}", collected.fill_ratio());
+        assert_eq!(collected.contains("12345"), BloomFilterContainsResponse::Maybe);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    hash::mix64, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher, Bytes, Hashed,
+    KeyEncoding, Xxh3,
+};
+use std::{borrow::Borrow, hash::Hash, marker::PhantomData};
+
+const BLOCK_BITS: usize = 512;
+
+/// 512 bits, aligned to a 64-byte cache line.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(align(64))]
+struct Block([u64; BLOCK_BITS / 64]);
+
+/// A bloom filter that maps each value to a single 512-bit block and sets
+/// all K bits within it, so every insert or lookup touches one cache line
+/// instead of up to K. The uneven load across blocks makes the
+/// false-positive rate slightly higher than a `BloomFilter` of the same
+/// size.
+pub struct BlockedBloomFilter<T, E = Bytes, S = Xxh3> {
+    blocks: Vec<Block>,
+    hashes: usize,
+    hasher: S,
+    _phantom: PhantomData<(T, E)>,
+}
+
+impl<T: AsRef<[u8]>> BlockedBloomFilter<T> {
+    /// Creates a new BlockedBloomFilter with the default arguments.
+    pub fn new() -> Self {
+        BlockedBloomFilter::with(BloomFilterArgs::default())
+    }
+
+    /// Creates a new BlockedBloomFilter with the given arguments. The
+    /// number of bits is rounded up to a whole number of blocks.
+    pub fn with(args: BloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for BlockedBloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> BlockedBloomFilter<T, Hashed> {
+    /// Creates a new BlockedBloomFilter over `Hash` values with the default
+    /// arguments.
+    pub fn new_hashed() -> Self {
+        BlockedBloomFilter::with_hashed(BloomFilterArgs::default())
+    }
+
+    /// Creates a new BlockedBloomFilter over `Hash` values with the given
+    /// arguments.
+    pub fn with_hashed(args: BloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> BlockedBloomFilter<T, E, S> {
+    /// Creates a new BlockedBloomFilter with the given arguments and hash
+    /// strategy.
+    pub fn with_hasher(args: BloomFilterArgs, hasher: S) -> Self {
+        Self {
+            blocks: vec![Block::default(); args.bits.div_ceil(BLOCK_BITS)],
+            hashes: args.hashes,
+            hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The number of bits (m) in the BlockedBloomFilter, a multiple of 512.
+    pub fn bits(&self) -> usize {
+        self.blocks.len() * BLOCK_BITS
+    }
+
+    /// The number of hash functions (k) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Inserts a new value into the BlockedBloomFilter.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (block, indices) = self.locate(value);
+        let block = &mut self.blocks[block];
+        for idx in indices {
+            block.0[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    /// Checks if the BlockedBloomFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (block, indices) = self.locate(value);
+        let block = &self.blocks[block];
+        for idx in indices {
+            if block.0[idx / 64] & (1 << (idx % 64)) == 0 {
+                return BloomFilterContainsResponse::No;
+            }
+        }
+        BloomFilterContainsResponse::Maybe
+    }
+
+    /// Picks the block of a value from h1, and the K bits within the block
+    /// from the top 9 bits of h2 + i * step.
+    fn locate<Q>(&self, value: &Q) -> (usize, impl Iterator<Item = usize>)
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (h1, h2) = E::hash_pair(value, &self.hasher);
+        // Multiply-shift maps h1 onto the blocks without a division.
+        let block = ((h1 as u128 * self.blocks.len() as u128) >> 64) as usize;
+        let step = mix64(h2) | 1;
+        let indices = (0..self.hashes as u64)
+            .map(move |i| (h2.wrapping_add(i.wrapping_mul(step)) >> 55) as usize);
+        (block, indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockedBloomFilter;
+    use crate::{BloomFilterArgs, BloomFilterContainsResponse, Hashed};
+
+    #[test]
+    fn blocked_bloom_filter_rounds_up_to_whole_blocks() {
+        let blocked: BlockedBloomFilter<String> =
+            BlockedBloomFilter::with(BloomFilterArgs::builder().bits(1_000).build());
+        assert_eq!(blocked.bits(), 1_024);
+        assert_eq!(blocked.hashes(), 3);
+    }
+
+    #[test]
+    fn blocked_bloom_filter_has_no_false_negatives_and_bounded_false_positives() {
+        let args = BloomFilterArgs::builder()
+            .expected_items(10_000)
+            .false_positive_rate(0.01)
+            .build();
+        let mut blocked: BlockedBloomFilter<u32, Hashed> = BlockedBloomFilter::with_hashed(args);
+        (0..10_000).for_each(|i| blocked.insert(&i));
+
+        assert!((0..10_000).all(|i| blocked.contains(&i) == BloomFilterContainsResponse::Maybe));
+        let false_positives = (10_000..110_000)
+            .filter(|i| blocked.contains(i) == BloomFilterContainsResponse::Maybe)
+            .count();
+        // Blocking costs some accuracy, but stays in the same ballpark.
+        assert!(false_positives < 2_000, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn blocked_bloom_filter_sets_all_bits_in_one_block() {
+        let mut blocked: BlockedBloomFilter<String> =
+            BlockedBloomFilter::with(BloomFilterArgs::builder().bits(512 * 16).hashes(8).build());
+        blocked.insert("one block");
+        let touched = blocked.blocks.iter().filter(|block| block.0 != [0; 8]).count();
+        assert_eq!(touched, 1);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    hash::HashIndices, BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher,
+    Bytes, Hashed, KeyEncoding, Xxh3,
+};
+use bit_vec::BitVec;
+use std::{borrow::Borrow, hash::Hash, marker::PhantomData};
+
+/// The width of each counter of a CountingBloomFilter. Counters saturate
+/// at their maximum and are never decremented afterwards, since the true
+/// count is no longer known.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CounterWidth {
+    #[default]
+    Four,
+    Eight,
+    Sixteen,
+}
+
+impl CounterWidth {
+    /// The number of bits in each counter.
+    pub fn bits(self) -> usize {
+        match self {
+            CounterWidth::Four => 4,
+            CounterWidth::Eight => 8,
+            CounterWidth::Sixteen => 16,
+        }
+    }
+
+    /// The value at which counters saturate.
+    pub fn max(self) -> u64 {
+        (1 << self.bits()) - 1
+    }
+}
+
+/// A bloom filter storing a small counter instead of a single bit in each
+/// slot, which allows values to be removed again.
+pub struct CountingBloomFilter<T, E = Bytes, S = Xxh3> {
+    counters: Vec<u64>,
+    len: usize,
+    width: CounterWidth,
+    hashes: usize,
+    hasher: S,
+    _phantom: PhantomData<(T, E)>,
+}
+
+impl<T: AsRef<[u8]>> CountingBloomFilter<T> {
+    /// Creates a new CountingBloomFilter with the default arguments and
+    /// 4-bit counters.
+    pub fn new() -> Self {
+        CountingBloomFilter::with(BloomFilterArgs::default(), CounterWidth::default())
+    }
+
+    /// Creates a new CountingBloomFilter with the given arguments, using
+    /// one counter of the given width per bit.
+    pub fn with(args: BloomFilterArgs, width: CounterWidth) -> Self {
+        Self::with_hasher(args, width, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for CountingBloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> CountingBloomFilter<T, Hashed> {
+    /// Creates a new CountingBloomFilter over `Hash` values with the
+    /// default arguments and 4-bit counters.
+    pub fn new_hashed() -> Self {
+        CountingBloomFilter::with_hashed(BloomFilterArgs::default(), CounterWidth::default())
+    }
+
+    /// Creates a new CountingBloomFilter over `Hash` values with the given
+    /// arguments and counter width.
+    pub fn with_hashed(args: BloomFilterArgs, width: CounterWidth) -> Self {
+        Self::with_hasher(args, width, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> CountingBloomFilter<T, E, S> {
+    /// Creates a new CountingBloomFilter with the given arguments, counter
+    /// width and hash strategy.
+    pub fn with_hasher(args: BloomFilterArgs, width: CounterWidth, hasher: S) -> Self {
+        let per_word = 64 / width.bits();
+        Self {
+            counters: vec![0; args.bits.div_ceil(per_word)],
+            len: args.bits,
+            width,
+            hashes: args.hashes,
+            hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The number of counters (m) in the CountingBloomFilter.
+    pub fn bits(&self) -> usize {
+        self.len
+    }
+
+    /// The number of hash functions (k) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// The width of each counter.
+    pub fn counter_width(&self) -> CounterWidth {
+        self.width
+    }
+
+    /// Inserts a new value into the CountingBloomFilter.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        for idx in self.calculate_hash_indices(value) {
+            let count = self.counter(idx);
+            if count < self.width.max() {
+                self.set_counter(idx, count + 1);
+            }
+        }
+    }
+
+    /// Removes a value from the CountingBloomFilter. Returns "no" and leaves
+    /// the filter untouched if the value was definitely not present.
+    ///
+    /// Only remove values that were inserted: removing a false positive
+    /// decrements counters of other values and may cause false negatives.
+    pub fn remove<Q>(&mut self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        if self.contains(value) == BloomFilterContainsResponse::No {
+            return BloomFilterContainsResponse::No;
+        }
+        for idx in self.calculate_hash_indices(value) {
+            let count = self.counter(idx);
+            // Removing a false positive whose indices repeat could otherwise
+            // underflow a counter.
+            if count > 0 && count < self.width.max() {
+                self.set_counter(idx, count - 1);
+            }
+        }
+        BloomFilterContainsResponse::Maybe
+    }
+
+    /// Checks if the CountingBloomFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        for idx in self.calculate_hash_indices(value) {
+            if self.counter(idx) == 0 {
+                return BloomFilterContainsResponse::No;
+            }
+        }
+        BloomFilterContainsResponse::Maybe
+    }
+
+    /// Converts the CountingBloomFilter into a plain BloomFilter with the
+    /// same parameters, setting every bit whose counter is non-zero.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, E, S>
+    where
+        S: Clone,
+    {
+        BloomFilter {
+            bits: self.occupied_bits(),
+            hashes: self.hashes,
+            hasher: self.hasher.clone(),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn occupied_bits(&self) -> BitVec {
+        BitVec::from_fn(self.len, |idx| self.counter(idx) > 0)
+    }
+
+    fn counter(&self, idx: usize) -> u64 {
+        let (word, shift) = self.position(idx);
+        (self.counters[word] >> shift) & self.width.max()
+    }
+
+    fn set_counter(&mut self, idx: usize, count: u64) {
+        let (word, shift) = self.position(idx);
+        let mask = self.width.max() << shift;
+        self.counters[word] = (self.counters[word] & !mask) | (count << shift);
+    }
+
+    fn position(&self, idx: usize) -> (usize, usize) {
+        let per_word = 64 / self.width.bits();
+        (idx / per_word, (idx % per_word) * self.width.bits())
+    }
+
+    fn calculate_hash_indices<Q>(&self, value: &Q) -> HashIndices
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        HashIndices::new(E::hash_pair(value, &self.hasher), self.hashes, self.len)
+    }
+}
+
+impl<T, E, S: BloomHasher> From<CountingBloomFilter<T, E, S>> for BloomFilter<T, E, S> {
+    fn from(counting: CountingBloomFilter<T, E, S>) -> Self {
+        BloomFilter {
+            bits: counting.occupied_bits(),
+            hashes: counting.hashes,
+            hasher: counting.hasher,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CounterWidth, CountingBloomFilter};
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, Hashed};
+
+    #[test]
+    fn counting_bloom_filter_supports_removal() {
+        let mut counting: CountingBloomFilter<String> = CountingBloomFilter::new();
+        counting.insert("revoked");
+        counting.insert("also revoked");
+        assert_eq!(counting.contains("revoked"), BloomFilterContainsResponse::Maybe);
+
+        assert_eq!(counting.remove("revoked"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(counting.contains("revoked"), BloomFilterContainsResponse::No);
+        assert_eq!(counting.contains("also revoked"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(counting.remove("never inserted"), BloomFilterContainsResponse::No);
+        assert_eq!(counting.contains("also revoked"), BloomFilterContainsResponse::Maybe);
+    }
+
+    #[test]
+    fn counting_bloom_filter_counts_repeated_inserts() {
+        let mut counting: CountingBloomFilter<u32, Hashed> = CountingBloomFilter::new_hashed();
+        counting.insert(&7);
+        counting.insert(&7);
+        counting.remove(&7);
+        assert_eq!(counting.contains(&7), BloomFilterContainsResponse::Maybe);
+        counting.remove(&7);
+        assert_eq!(counting.contains(&7), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn counting_bloom_filter_counters_saturate() {
+        for width in [CounterWidth::Four, CounterWidth::Eight, CounterWidth::Sixteen] {
+            let args = BloomFilterArgs::builder().bits(100).hashes(3).build();
+            let mut counting: CountingBloomFilter<String> = CountingBloomFilter::with(args, width);
+            let saturating = width.max() as usize + 5;
+            (0..saturating).for_each(|_| counting.insert("hot"));
+            (0..saturating).for_each(|_| {
+                counting.remove("hot");
+            });
+            // Saturated counters no longer know their count, so they stick.
+            assert_eq!(counting.contains("hot"), BloomFilterContainsResponse::Maybe);
+            assert_eq!(counting.counter_width(), width);
+        }
+    }
+
+    #[test]
+    fn counting_bloom_filter_converts_to_bloom_filter() {
+        let mut counting: CountingBloomFilter<String> = CountingBloomFilter::new();
+        let mut plain: BloomFilter<String> = BloomFilter::new();
+        for key in ["a", "b", "c"] {
+            counting.insert(key);
+            plain.insert(key);
+        }
+        counting.insert("d");
+        counting.remove("d");
+
+        assert_eq!(counting.to_bloom_filter().to_bytes(), plain.to_bytes());
+        assert_eq!(BloomFilter::from(counting).to_bytes(), plain.to_bytes());
+    }
+}
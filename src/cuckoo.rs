@@ -0,0 +1,406 @@
+use crate::{
+    hash::mix64, rng::SplitMix64, BloomFilterContainsResponse, BloomHasher, Bytes, Hashed,
+    KeyEncoding, Xxh3,
+};
+use std::{borrow::Borrow, fmt, hash::Hash, marker::PhantomData};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CuckooFilterArgs {
+    capacity: usize,
+    fingerprint_bits: u32,
+    bucket_size: usize,
+    max_kicks: usize,
+}
+
+impl Default for CuckooFilterArgs {
+    fn default() -> Self {
+        Self { capacity: 1024, fingerprint_bits: 12, bucket_size: 4, max_kicks: 500 }
+    }
+}
+
+impl CuckooFilterArgs {
+    /// Creates a builder for CuckooFilterArgs.
+    pub fn builder() -> CuckooFilterArgsBuilder {
+        CuckooFilterArgsBuilder::default()
+    }
+
+    /// The number of items the filter is sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of bits in each fingerprint (f).
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    /// The number of fingerprints each bucket holds (b).
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// How many fingerprints an insert may relocate before giving up.
+    pub fn max_kicks(&self) -> usize {
+        self.max_kicks
+    }
+}
+
+/// Builds CuckooFilterArgs. Anything left unset falls back to
+/// `CuckooFilterArgs::default()`.
+#[derive(Clone, Debug, Default)]
+pub struct CuckooFilterArgsBuilder {
+    capacity: Option<usize>,
+    fingerprint_bits: Option<u32>,
+    bucket_size: Option<usize>,
+    max_kicks: Option<usize>,
+}
+
+impl CuckooFilterArgsBuilder {
+    /// Sets the number of items the filter is sized for.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the number of bits in each fingerprint, between 1 and 16. The
+    /// false-positive rate is about 2b / 2^f.
+    pub fn fingerprint_bits(mut self, fingerprint_bits: u32) -> Self {
+        self.fingerprint_bits = Some(fingerprint_bits);
+        self
+    }
+
+    /// Sets the number of fingerprints each bucket holds, between 1 and 8.
+    pub fn bucket_size(mut self, bucket_size: usize) -> Self {
+        self.bucket_size = Some(bucket_size);
+        self
+    }
+
+    /// Sets how many fingerprints an insert may relocate before giving up.
+    pub fn max_kicks(mut self, max_kicks: usize) -> Self {
+        self.max_kicks = Some(max_kicks);
+        self
+    }
+
+    /// Builds the arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, `fingerprint_bits` is not between 1
+    /// and 16, or `bucket_size` is not between 1 and 8.
+    pub fn build(self) -> CuckooFilterArgs {
+        let default = CuckooFilterArgs::default();
+        let args = CuckooFilterArgs {
+            capacity: self.capacity.unwrap_or(default.capacity),
+            fingerprint_bits: self.fingerprint_bits.unwrap_or(default.fingerprint_bits),
+            bucket_size: self.bucket_size.unwrap_or(default.bucket_size),
+            max_kicks: self.max_kicks.unwrap_or(default.max_kicks),
+        };
+        assert!(args.capacity > 0, "capacity must be greater than zero");
+        assert!(
+            (1..=16).contains(&args.fingerprint_bits),
+            "fingerprint_bits must be between 1 and 16"
+        );
+        assert!((1..=8).contains(&args.bucket_size), "bucket_size must be between 1 and 8");
+        args
+    }
+}
+
+/// The error returned when a CuckooFilter has no room left for a value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CuckooFilterFull;
+
+impl fmt::Display for CuckooFilterFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cuckoo filter is full")
+    }
+}
+
+impl std::error::Error for CuckooFilterFull {}
+
+/// A cuckoo filter (Fan et al., "Cuckoo Filter: Practically Better Than
+/// Bloom"). Stores a short fingerprint of each value in one of two
+/// candidate buckets, which allows removal and takes less space than a
+/// `BloomFilter` for false-positive rates below about 3%.
+pub struct CuckooFilter<T, E = Bytes, S = Xxh3> {
+    /// `buckets * bucket_size` fingerprints, zero meaning empty.
+    slots: Vec<u16>,
+    bucket_mask: usize,
+    /// A fingerprint evicted by the last failed relocation, with its
+    /// bucket. Once set, the filter is full.
+    victim: Option<(usize, u16)>,
+    len: usize,
+    args: CuckooFilterArgs,
+    hasher: S,
+    rng: SplitMix64,
+    _phantom: PhantomData<(T, E)>,
+}
+
+impl<T: AsRef<[u8]>> CuckooFilter<T> {
+    /// Creates a new CuckooFilter with the default arguments.
+    pub fn new() -> Self {
+        CuckooFilter::with(CuckooFilterArgs::default())
+    }
+
+    /// Creates a new CuckooFilter with the given arguments.
+    pub fn with(args: CuckooFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for CuckooFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> CuckooFilter<T, Hashed> {
+    /// Creates a new CuckooFilter over `Hash` values with the default
+    /// arguments.
+    pub fn new_hashed() -> Self {
+        CuckooFilter::with_hashed(CuckooFilterArgs::default())
+    }
+
+    /// Creates a new CuckooFilter over `Hash` values with the given
+    /// arguments.
+    pub fn with_hashed(args: CuckooFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> CuckooFilter<T, E, S> {
+    /// Creates a new CuckooFilter with the given arguments and hash
+    /// strategy. The number of buckets is a power of two, with room for
+    /// the capacity at a load factor of at most 95%.
+    pub fn with_hasher(args: CuckooFilterArgs, hasher: S) -> Self {
+        let mut buckets = args.capacity.div_ceil(args.bucket_size).next_power_of_two();
+        if args.capacity as f64 / (buckets * args.bucket_size) as f64 > 0.95 {
+            buckets *= 2;
+        }
+        Self {
+            slots: vec![0; buckets * args.bucket_size],
+            bucket_mask: buckets - 1,
+            victim: None,
+            len: 0,
+            args,
+            hasher,
+            rng: SplitMix64::new(0x6375_636b_6f6f),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The arguments of the CuckooFilter.
+    pub fn args(&self) -> &CuckooFilterArgs {
+        &self.args
+    }
+
+    /// The number of fingerprints stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no fingerprints are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of fingerprints the table has slots for.
+    pub fn slots(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Inserts a new value into the CuckooFilter.
+    ///
+    /// When both candidate buckets are full, fingerprints are relocated to
+    /// their alternate buckets. If that fails after `max_kicks` moves, the
+    /// last evicted fingerprint is kept aside so no value is lost, and every
+    /// further insert returns `CuckooFilterFull` until something is removed.
+    pub fn insert<Q>(&mut self, value: &Q) -> Result<(), CuckooFilterFull>
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        if self.victim.is_some() {
+            return Err(CuckooFilterFull);
+        }
+        let (bucket, fingerprint) = self.locate(value);
+        self.place(bucket, fingerprint);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes one copy of a value from the CuckooFilter. Returns "no" and
+    /// leaves the filter untouched if the value was definitely not present.
+    ///
+    /// Only remove values that were inserted: removing a false positive
+    /// deletes the fingerprint of another value.
+    pub fn remove<Q>(&mut self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (bucket, fingerprint) = self.locate(value);
+        let alternate = self.alternate(bucket, fingerprint);
+        if self.victim.is_some_and(|victim| {
+            victim.1 == fingerprint && (victim.0 == bucket || victim.0 == alternate)
+        }) {
+            self.victim = None;
+        } else if let Some(slot) =
+            [bucket, alternate].into_iter().find_map(|bucket| self.find(bucket, fingerprint))
+        {
+            self.slots[slot] = 0;
+            // Make room for the fingerprint kept aside by a failed insert.
+            if let Some((bucket, fingerprint)) = self.victim.take() {
+                self.place(bucket, fingerprint);
+            }
+        } else {
+            return BloomFilterContainsResponse::No;
+        }
+        self.len -= 1;
+        BloomFilterContainsResponse::Maybe
+    }
+
+    /// Checks if the CuckooFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (bucket, fingerprint) = self.locate(value);
+        let alternate = self.alternate(bucket, fingerprint);
+        let found = self.find(bucket, fingerprint).is_some()
+            || self.find(alternate, fingerprint).is_some()
+            || self.victim.is_some_and(|victim| {
+                victim.1 == fingerprint && (victim.0 == bucket || victim.0 == alternate)
+            });
+        if found {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+
+    /// Stores a fingerprint in its bucket or the alternate one, relocating
+    /// other fingerprints if both are full.
+    fn place(&mut self, bucket: usize, fingerprint: u16) {
+        let alternate = self.alternate(bucket, fingerprint);
+        for bucket in [bucket, alternate] {
+            if let Some(slot) = self.find(bucket, 0) {
+                self.slots[slot] = fingerprint;
+                return;
+            }
+        }
+
+        let (mut bucket, mut fingerprint) =
+            if self.rng.below(2) == 0 { (bucket, fingerprint) } else { (alternate, fingerprint) };
+        for _ in 0..self.args.max_kicks {
+            let slot = bucket * self.args.bucket_size + self.rng.below(self.args.bucket_size);
+            std::mem::swap(&mut fingerprint, &mut self.slots[slot]);
+            bucket = self.alternate(bucket, fingerprint);
+            if let Some(slot) = self.find(bucket, 0) {
+                self.slots[slot] = fingerprint;
+                return;
+            }
+        }
+        self.victim = Some((bucket, fingerprint));
+    }
+
+    /// The slot in the bucket holding the fingerprint.
+    fn find(&self, bucket: usize, fingerprint: u16) -> Option<usize> {
+        let start = bucket * self.args.bucket_size;
+        (start..start + self.args.bucket_size).find(|&slot| self.slots[slot] == fingerprint)
+    }
+
+    /// The primary bucket and the non-zero fingerprint of a value.
+    fn locate<Q>(&self, value: &Q) -> (usize, u16)
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (h1, h2) = E::hash_pair(value, &self.hasher);
+        let mask = (1u64 << self.args.fingerprint_bits) - 1;
+        let fingerprint = ((h2 & mask) as u16).max(1);
+        (h1 as usize & self.bucket_mask, fingerprint)
+    }
+
+    /// Partial-key cuckoo hashing: the other bucket of a fingerprint is
+    /// found from either bucket and the fingerprint alone.
+    fn alternate(&self, bucket: usize, fingerprint: u16) -> usize {
+        (bucket ^ mix64(fingerprint as u64) as usize) & self.bucket_mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CuckooFilter, CuckooFilterArgs, CuckooFilterFull};
+    use crate::{BloomFilterContainsResponse, Hashed};
+
+    #[test]
+    fn cuckoo_filter_inserts_and_removes() {
+        let mut cuckoo: CuckooFilter<String> = CuckooFilter::new();
+        cuckoo.insert("a").unwrap();
+        cuckoo.insert("b").unwrap();
+        assert_eq!(cuckoo.len(), 2);
+        assert_eq!(cuckoo.contains("a"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(cuckoo.contains("c"), BloomFilterContainsResponse::No);
+
+        assert_eq!(cuckoo.remove("a"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(cuckoo.contains("a"), BloomFilterContainsResponse::No);
+        assert_eq!(cuckoo.contains("b"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(cuckoo.remove("a"), BloomFilterContainsResponse::No);
+        assert_eq!(cuckoo.len(), 1);
+    }
+
+    #[test]
+    fn cuckoo_filter_holds_its_capacity_without_false_negatives() {
+        let args = CuckooFilterArgs::builder().capacity(10_000).build();
+        let mut cuckoo: CuckooFilter<u32, Hashed> = CuckooFilter::with_hashed(args);
+        for i in 0..10_000 {
+            cuckoo.insert(&i).unwrap();
+        }
+        assert!((0..10_000).all(|i| cuckoo.contains(&i) == BloomFilterContainsResponse::Maybe));
+
+        // About 2b / 2^f = 8 / 4096.
+        let false_positives = (10_000..110_000)
+            .filter(|i| cuckoo.contains(i) == BloomFilterContainsResponse::Maybe)
+            .count();
+        assert!(false_positives < 400, "{} false positives", false_positives);
+
+        for i in 0..5_000 {
+            assert_eq!(cuckoo.remove(&i), BloomFilterContainsResponse::Maybe);
+        }
+        assert!((5_000..10_000).all(|i| cuckoo.contains(&i) == BloomFilterContainsResponse::Maybe));
+    }
+
+    #[test]
+    fn cuckoo_filter_reports_full_table() {
+        let args =
+            CuckooFilterArgs::builder().capacity(8).bucket_size(2).fingerprint_bits(16).build();
+        let mut cuckoo: CuckooFilter<u32, Hashed> = CuckooFilter::with_hashed(args);
+        let inserted: Vec<u32> = (0..).take_while(|i| cuckoo.insert(i).is_ok()).collect();
+        assert!(inserted.len() > cuckoo.slots() / 2);
+        assert_eq!(cuckoo.insert(&1_000), Err(CuckooFilterFull));
+        // Nothing that was accepted is lost, including the kept-aside victim.
+        assert!(inserted.iter().all(|i| cuckoo.contains(i) == BloomFilterContainsResponse::Maybe));
+
+        // Removing a value makes room again.
+        assert_eq!(cuckoo.remove(&inserted[0]), BloomFilterContainsResponse::Maybe);
+        assert!(inserted[1..]
+            .iter()
+            .all(|i| cuckoo.contains(i) == BloomFilterContainsResponse::Maybe));
+        assert_eq!(cuckoo.insert(&inserted[0]), Ok(()));
+    }
+
+    #[test]
+    fn cuckoo_filter_stores_duplicates() {
+        let mut cuckoo: CuckooFilter<String> = CuckooFilter::new();
+        cuckoo.insert("twice").unwrap();
+        cuckoo.insert("twice").unwrap();
+        cuckoo.remove("twice");
+        assert_eq!(cuckoo.contains("twice"), BloomFilterContainsResponse::Maybe);
+        cuckoo.remove("twice");
+        assert_eq!(cuckoo.contains("twice"), BloomFilterContainsResponse::No);
+    }
+}
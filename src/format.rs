@@ -0,0 +1,351 @@
+//! The binary format of a `BloomFilter`.
+//!
+//! All integers are little-endian.
+//!
+//! | Offset | Size | Field                                          |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 4    | Magic, `b"BLMF"`                               |
+//! | 4      | 1    | Format version, currently 1                    |
+//! | 5      | 1    | Hash scheme id (see `PortableHasher`)          |
+//! | 6      | 2    | Reserved, zero                                 |
+//! | 8      | 8    | Number of bits (m)                             |
+//! | 16     | 4    | Number of hash functions (k)                   |
+//! | 20     | 4    | Reserved, zero                                 |
+//! | 24     | 8    | Hash seed                                      |
+//! | 32     | n    | Bits, n = ceil(m / 8), most significant first  |
+//! | 32 + n | 8    | XXH3-64 (seed 0) of all preceding bytes        |
+//!
+//! Bit i of the filter is stored in byte i / 8 under the mask
+//! `0x80 >> (i % 8)`. Padding bits in the last byte are zero.
+
+use crate::{BloomFilter, PortableHasher};
+use bit_vec::BitVec;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+pub(crate) const MAGIC: [u8; 4] = *b"BLMF";
+pub(crate) const VERSION: u8 = 1;
+pub(crate) const HEADER_LEN: usize = 32;
+pub(crate) const CHECKSUM_LEN: usize = 8;
+
+/// An error decoding a serialized `BloomFilter`.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The input ended before the filter was complete.
+    Truncated,
+    /// The input does not start with the format's magic bytes.
+    BadMagic,
+    /// The format version is not supported by this library.
+    UnsupportedVersion(u8),
+    /// The filter was built with a different hash scheme than requested.
+    SchemeMismatch { expected: u8, found: u8 },
+    /// The header holds values no valid filter can have.
+    InvalidHeader(&'static str),
+    /// The input has a length no valid filter can have.
+    InvalidLength(usize),
+    /// The checksum does not match the contents.
+    ChecksumMismatch,
+    /// The input continues after the checksum.
+    TrailingBytes,
+    /// Reading the input failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "bloom filter data is truncated"),
+            DecodeError::BadMagic => write!(f, "not a bloom filter: bad magic bytes"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported bloom filter format version {}", version)
+            }
+            DecodeError::SchemeMismatch { expected, found } => write!(
+                f,
+                "bloom filter uses hash scheme {}, expected scheme {}",
+                found, expected
+            ),
+            DecodeError::InvalidHeader(reason) => write!(f, "invalid bloom filter header: {}", reason),
+            DecodeError::InvalidLength(len) => write!(f, "invalid bloom filter length of {} bytes", len),
+            DecodeError::ChecksumMismatch => write!(f, "bloom filter checksum mismatch"),
+            DecodeError::TrailingBytes => write!(f, "unexpected bytes after bloom filter"),
+            DecodeError::Io(err) => write!(f, "failed to read bloom filter: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => DecodeError::Truncated,
+            _ => DecodeError::Io(err),
+        }
+    }
+}
+
+/// The fixed-size header of the format.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Header {
+    pub(crate) scheme: u8,
+    pub(crate) bits: usize,
+    pub(crate) hashes: usize,
+    pub(crate) seed: u64,
+}
+
+impl Header {
+    pub(crate) fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4] = VERSION;
+        header[5] = self.scheme;
+        header[8..16].copy_from_slice(&(self.bits as u64).to_le_bytes());
+        header[16..20].copy_from_slice(&(self.hashes as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&self.seed.to_le_bytes());
+        header
+    }
+
+    pub(crate) fn decode(header: &[u8; HEADER_LEN]) -> Result<Self, DecodeError> {
+        if header[0..4] != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        if header[4] != VERSION {
+            return Err(DecodeError::UnsupportedVersion(header[4]));
+        }
+        if header[6..8] != [0; 2] || header[20..24] != [0; 4] {
+            return Err(DecodeError::InvalidHeader("reserved bytes are not zero"));
+        }
+
+        Self::new(
+            header[5],
+            u64::from_le_bytes(header[8..16].try_into().unwrap()),
+            u32::from_le_bytes(header[16..20].try_into().unwrap()),
+            u64::from_le_bytes(header[24..32].try_into().unwrap()),
+        )
+    }
+
+    /// Validates the fields of a header.
+    pub(crate) fn new(scheme: u8, bits: u64, hashes: u32, seed: u64) -> Result<Self, DecodeError> {
+        if bits == 0 {
+            return Err(DecodeError::InvalidHeader("bit count is zero"));
+        }
+        if hashes == 0 {
+            return Err(DecodeError::InvalidHeader("hash count is zero"));
+        }
+        let bits = usize::try_from(bits)
+            .map_err(|_| DecodeError::InvalidHeader("bit count does not fit in memory"))?;
+
+        Ok(Self { scheme, bits, hashes: hashes as usize, seed })
+    }
+
+    /// Checks that the header was written with the strategy `S`.
+    pub(crate) fn check_scheme<S: PortableHasher>(&self) -> Result<(), DecodeError> {
+        if self.scheme != S::SCHEME {
+            return Err(DecodeError::SchemeMismatch { expected: S::SCHEME, found: self.scheme });
+        }
+        Ok(())
+    }
+
+    /// The number of payload bytes following the header.
+    pub(crate) fn payload_len(&self) -> usize {
+        self.bits.div_ceil(8)
+    }
+}
+
+impl<T, E, S: PortableHasher> BloomFilter<T, E, S> {
+    /// Serializes the BloomFilter into the binary format described in the
+    /// `format` module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = self.header();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + header.payload_len() + CHECKSUM_LEN);
+        bytes.extend_from_slice(&header.encode());
+        bytes.extend_from_slice(&self.bits.to_bytes());
+        let checksum = xxh3_64(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Writes the BloomFilter in the binary format to the given writer.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let header = self.header().encode();
+        let payload = self.bits.to_bytes();
+        let mut checksum = Xxh3::new();
+        checksum.update(&header);
+        checksum.update(&payload);
+
+        writer.write_all(&header)?;
+        writer.write_all(&payload)?;
+        writer.write_all(&checksum.digest().to_le_bytes())
+    }
+
+    /// Deserializes a BloomFilter from the binary format. The input must
+    /// hold exactly one filter built with the hash scheme of `S`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let header: &[u8; HEADER_LEN] = bytes
+            .get(..HEADER_LEN)
+            .ok_or(DecodeError::Truncated)?
+            .try_into()
+            .unwrap();
+        let header = Header::decode(header)?;
+        header.check_scheme::<S>()?;
+
+        let end = HEADER_LEN + header.payload_len();
+        if bytes.len() < end + CHECKSUM_LEN {
+            return Err(DecodeError::Truncated);
+        }
+        if bytes.len() > end + CHECKSUM_LEN {
+            return Err(DecodeError::TrailingBytes);
+        }
+        let checksum = u64::from_le_bytes(bytes[end..].try_into().unwrap());
+        if xxh3_64(&bytes[..end]) != checksum {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        Ok(Self::from_parts(&header, &bytes[HEADER_LEN..end]))
+    }
+
+    /// Reads a BloomFilter in the binary format from the given reader,
+    /// consuming exactly the bytes of one filter.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, DecodeError> {
+        let mut header_bytes = [0; HEADER_LEN];
+        reader.read_exact(&mut header_bytes)?;
+        let header = Header::decode(&header_bytes)?;
+        header.check_scheme::<S>()?;
+
+        // Read through `take` so a corrupted length can't make us allocate
+        // more than the input actually holds.
+        let mut payload = Vec::new();
+        let len = header.payload_len() as u64;
+        if reader.by_ref().take(len).read_to_end(&mut payload)? as u64 != len {
+            return Err(DecodeError::Truncated);
+        }
+        let mut checksum = [0; CHECKSUM_LEN];
+        reader.read_exact(&mut checksum)?;
+
+        let mut expected = Xxh3::new();
+        expected.update(&header_bytes);
+        expected.update(&payload);
+        if expected.digest() != u64::from_le_bytes(checksum) {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        Ok(Self::from_parts(&header, &payload))
+    }
+
+    fn header(&self) -> Header {
+        Header {
+            scheme: S::SCHEME,
+            bits: self.bits.len(),
+            hashes: self.hashes,
+            seed: self.hasher.seed(),
+        }
+    }
+
+    pub(crate) fn from_parts(header: &Header, payload: &[u8]) -> Self {
+        let mut bits = BitVec::from_bytes(payload);
+        bits.truncate(header.bits);
+        Self {
+            bits,
+            hashes: header.hashes,
+            hasher: S::with_seed(header.seed),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, HEADER_LEN};
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, Bytes, PortableHasher, Xxh3};
+
+    fn filter() -> BloomFilter<String, Bytes, Xxh3> {
+        let args = BloomFilterArgs::builder().bits(100).hashes(4).build();
+        let mut bloom_filter = BloomFilter::with_hasher(args, Xxh3::with_seed(9));
+        ["alpha", "beta", "gamma"].iter().for_each(|&s| bloom_filter.insert(s));
+        bloom_filter
+    }
+
+    #[test]
+    fn bloom_filter_round_trips_through_bytes() {
+        let bytes = filter().to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 13 + 8);
+        assert_eq!(&bytes[..8], b"BLMF\x01\x01\x00\x00");
+        assert_eq!(&bytes[8..32], &[
+            100, 0, 0, 0, 0, 0, 0, 0,
+            4, 0, 0, 0, 0, 0, 0, 0,
+            9, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+
+        let decoded: BloomFilter<String> = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.bits(), 100);
+        assert_eq!(decoded.hashes(), 4);
+        assert_eq!(decoded.hasher().seed(), 9);
+        assert_eq!(decoded.to_bytes(), bytes);
+        for key in ["alpha", "beta", "gamma"] {
+            assert_eq!(decoded.contains(key), BloomFilterContainsResponse::Maybe);
+        }
+    }
+
+    #[test]
+    fn bloom_filter_round_trips_through_io() {
+        let mut buffer = Vec::new();
+        filter().write_to(&mut buffer).unwrap();
+        assert_eq!(buffer, filter().to_bytes());
+
+        buffer.extend_from_slice(b"next record");
+        let mut reader = &buffer[..];
+        let decoded: BloomFilter<String> = BloomFilter::read_from(&mut reader).unwrap();
+        assert_eq!(decoded.to_bytes(), filter().to_bytes());
+        assert_eq!(reader, b"next record");
+    }
+
+    #[test]
+    fn bloom_filter_decode_rejects_bad_input() {
+        let bytes = filter().to_bytes();
+        let decode = |bytes: &[u8]| BloomFilter::<String>::from_bytes(bytes).err().unwrap();
+
+        assert!(matches!(decode(&bytes[..10]), DecodeError::Truncated));
+        assert!(matches!(decode(&bytes[..bytes.len() - 1]), DecodeError::Truncated));
+        assert!(matches!(
+            BloomFilter::<String>::read_from(&bytes[..bytes.len() - 1]).err().unwrap(),
+            DecodeError::Truncated
+        ));
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(matches!(decode(&extended), DecodeError::TrailingBytes));
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert!(matches!(decode(&corrupted), DecodeError::BadMagic));
+
+        let mut corrupted = bytes.clone();
+        corrupted[4] = 2;
+        assert!(matches!(decode(&corrupted), DecodeError::UnsupportedVersion(2)));
+
+        let mut corrupted = bytes.clone();
+        corrupted[5] = 7;
+        assert!(matches!(decode(&corrupted), DecodeError::SchemeMismatch { expected: 1, found: 7 }));
+
+        let mut corrupted = bytes.clone();
+        corrupted[16..20].copy_from_slice(&[0; 4]);
+        assert!(matches!(decode(&corrupted), DecodeError::InvalidHeader(_)));
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 3] ^= 0x10;
+        assert!(matches!(decode(&corrupted), DecodeError::ChecksumMismatch));
+        assert!(matches!(
+            BloomFilter::<String>::read_from(&corrupted[..]).err().unwrap(),
+            DecodeError::ChecksumMismatch
+        ));
+    }
+}
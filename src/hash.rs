@@ -0,0 +1,392 @@
+use fnv::FnvHasher;
+use fxhash::FxHasher;
+use sha2::Digest;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{BuildHasher, BuildHasherDefault, Hasher},
+};
+use xxhash_rust::xxh3::{xxh3_128_with_seed, Xxh3 as Xxh3Stream};
+
+/// A strategy producing the two base hashes (h1, h2) from which a value's
+/// K indices are derived.
+pub trait BloomHasher {
+    /// The incremental hasher values are fed into.
+    type Hasher: Hasher;
+
+    /// Creates a fresh hasher for a single value.
+    fn build_hasher(&self) -> Self::Hasher;
+
+    /// Finishes the hasher into the two base hashes.
+    fn finish_pair(hasher: &Self::Hasher) -> (u64, u64);
+
+    /// Hashes a contiguous byte slice into the two base hashes. Strategies
+    /// with a faster one-shot function override this.
+    fn hash_bytes(&self, bytes: &[u8]) -> (u64, u64) {
+        let mut hasher = self.build_hasher();
+        hasher.write(bytes);
+        Self::finish_pair(&hasher)
+    }
+}
+
+/// A strategy whose output is fully specified and will never change, so
+/// filters built with it stay valid across processes, platforms and Rust
+/// versions, provided the keys are fed in a stable way (see `Bytes`).
+///
+/// Every portable strategy is identified by a scheme id recorded alongside
+/// the filter's seed. A scheme id is never reused for a different
+/// algorithm; changing how h1 and h2 are derived requires a new id.
+///
+/// | Scheme | Strategy | h1, h2 |
+/// |--------|----------|--------|
+/// | 1      | `Xxh3`   | low and high 64 bits of XXH3-128 with the seed |
+pub trait PortableHasher: BloomHasher {
+    /// The scheme id of the strategy.
+    const SCHEME: u8;
+
+    /// Creates the strategy with the given seed.
+    fn with_seed(seed: u64) -> Self;
+
+    /// The seed of the strategy.
+    fn seed(&self) -> u64;
+}
+
+/// XXH3-128 with an explicit seed, splitting the digest into h1 (low 64
+/// bits) and h2 (high 64 bits). Fast, stable and portable (scheme 1).
+/// This is the default strategy.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Xxh3 {
+    seed: u64,
+}
+
+impl BloomHasher for Xxh3 {
+    type Hasher = Xxh3Hasher;
+
+    fn build_hasher(&self) -> Xxh3Hasher {
+        Xxh3Hasher {
+            seed: self.seed,
+            buffer: [0; XXH3_BUFFER],
+            len: 0,
+            stream: None,
+        }
+    }
+
+    fn finish_pair(hasher: &Xxh3Hasher) -> (u64, u64) {
+        let digest = match &hasher.stream {
+            Some(stream) => stream.digest128(),
+            None => xxh3_128_with_seed(&hasher.buffer[..hasher.len], hasher.seed),
+        };
+        (digest as u64, (digest >> 64) as u64)
+    }
+
+    fn hash_bytes(&self, bytes: &[u8]) -> (u64, u64) {
+        let digest = xxh3_128_with_seed(bytes, self.seed);
+        (digest as u64, (digest >> 64) as u64)
+    }
+}
+
+impl PortableHasher for Xxh3 {
+    const SCHEME: u8 = 1;
+
+    fn with_seed(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+const XXH3_BUFFER: usize = 128;
+
+/// The hasher of the `Xxh3` strategy, used for keys fed in several writes.
+/// Short keys are buffered and hashed in one shot, since setting up a
+/// seeded streaming state costs more than hashing them; longer keys spill
+/// over into the streaming state.
+#[derive(Clone)]
+pub struct Xxh3Hasher {
+    seed: u64,
+    buffer: [u8; XXH3_BUFFER],
+    len: usize,
+    stream: Option<Xxh3Stream>,
+}
+
+impl Hasher for Xxh3Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            stream.update(bytes);
+        } else if self.len + bytes.len() <= XXH3_BUFFER {
+            self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        } else {
+            let mut stream = Xxh3Stream::with_seed(self.seed);
+            stream.update(&self.buffer[..self.len]);
+            stream.update(bytes);
+            self.stream = Some(stream);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        Xxh3::finish_pair(self).0
+    }
+}
+
+/// FNV-1a for h1 and FxHash for h2. Fast, but not resistant to
+/// adversarially chosen keys, and FxHash differs between 32 and 64-bit
+/// platforms.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FnvFx;
+
+impl BloomHasher for FnvFx {
+    type Hasher = FnvFxHasher;
+
+    fn build_hasher(&self) -> FnvFxHasher {
+        FnvFxHasher::default()
+    }
+
+    fn finish_pair(hasher: &FnvFxHasher) -> (u64, u64) {
+        (hasher.fnv.finish(), hasher.fx.finish())
+    }
+}
+
+/// The hasher of the `FnvFx` strategy, feeding every write to both hashers.
+#[derive(Default)]
+pub struct FnvFxHasher {
+    fnv: FnvHasher,
+    fx: FxHasher,
+}
+
+impl Hasher for FnvFxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.fnv.write(bytes);
+        self.fx.write(bytes);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.fnv.write_u8(i);
+        self.fx.write_u8(i);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.fnv.write_u16(i);
+        self.fx.write_u16(i);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.fnv.write_u32(i);
+        self.fx.write_u32(i);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.fnv.write_u64(i);
+        self.fx.write_u64(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.fnv.write_usize(i);
+        self.fx.write_usize(i);
+    }
+
+    fn finish(&self) -> u64 {
+        self.fnv.finish()
+    }
+}
+
+/// Derives h1 and h2 from the first 16 bytes of a SHA-256 digest. Much
+/// slower than `FnvFx`, but well distributed even for adversarial keys.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sha256;
+
+impl BloomHasher for Sha256 {
+    type Hasher = Sha256Hasher;
+
+    fn build_hasher(&self) -> Sha256Hasher {
+        Sha256Hasher::default()
+    }
+
+    fn finish_pair(hasher: &Sha256Hasher) -> (u64, u64) {
+        let digest = hasher.digest.clone().finalize();
+        let (h1, h2) = digest.split_at(8);
+        (
+            u64::from_le_bytes(h1.try_into().unwrap()),
+            u64::from_le_bytes(h2[..8].try_into().unwrap()),
+        )
+    }
+}
+
+/// The hasher of the `Sha256` strategy.
+#[derive(Clone, Default)]
+pub struct Sha256Hasher {
+    digest: sha2::Sha256,
+}
+
+impl Hasher for Sha256Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.digest.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        Sha256::finish_pair(self).0
+    }
+}
+
+/// Adapts any `BuildHasher` into a strategy. The builder only yields a
+/// single 64-bit hash, so h2 is derived from h1 with a bit mixer.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FromBuildHasher<B>(pub B);
+
+impl<B: BuildHasher> BloomHasher for FromBuildHasher<B> {
+    type Hasher = B::Hasher;
+
+    fn build_hasher(&self) -> B::Hasher {
+        self.0.build_hasher()
+    }
+
+    fn finish_pair(hasher: &B::Hasher) -> (u64, u64) {
+        let h1 = hasher.finish();
+        (h1, mix64(h1))
+    }
+}
+
+/// FNV-1a on its own.
+pub type Fnv = FromBuildHasher<BuildHasherDefault<FnvHasher>>;
+
+/// FxHash on its own.
+pub type Fx = FromBuildHasher<BuildHasherDefault<FxHasher>>;
+
+/// SipHash via the standard library's `DefaultHasher`.
+pub type Sip = FromBuildHasher<BuildHasherDefault<DefaultHasher>>;
+
+/// The SplitMix64 finalizer.
+pub(crate) fn mix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// An iterator over the K bit indices of a value, computed by
+/// Kirsch-Mitzenmacher double hashing (g_i = h1 + i * h2) without
+/// allocating. A step of zero modulo the number of bits would send every
+/// probe to the same bit, so it is replaced by a step of one.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HashIndices {
+    hash: u64,
+    step: u64,
+    remaining: usize,
+    bits: u64,
+}
+
+impl HashIndices {
+    pub(crate) fn new((h1, h2): (u64, u64), hashes: usize, bits: usize) -> Self {
+        let bits = bits as u64;
+        let step = if h2 % bits == 0 { 1 } else { h2 };
+        Self { hash: h1, step, remaining: hashes, bits }
+    }
+}
+
+impl Iterator for HashIndices {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let idx = (self.hash % self.bits) as usize;
+        self.hash = self.hash.wrapping_add(self.step);
+        self.remaining -= 1;
+        Some(idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for HashIndices {}
+
+#[cfg(test)]
+mod tests {
+    use super::HashIndices;
+    use crate::{
+        BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher, Bytes, Fnv,
+        FnvFx, FromBuildHasher, Fx, Hashed, PortableHasher, Sha256, Sip, Xxh3,
+    };
+    use std::collections::hash_map::RandomState;
+
+    fn assert_membership<S: BloomHasher>(hasher: S) {
+        let mut bloom_filter: BloomFilter<String, Bytes, S> =
+            BloomFilter::with_hasher(BloomFilterArgs::default(), hasher);
+        bloom_filter.insert("present");
+        assert_eq!(bloom_filter.contains("present"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(bloom_filter.contains("absent"), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn every_shipped_strategy_finds_inserted_values() {
+        assert_membership(Xxh3::default());
+        assert_membership(Xxh3::with_seed(42));
+        assert_membership(FnvFx);
+        assert_membership(Sha256);
+        assert_membership(Fnv::default());
+        assert_membership(Fx::default());
+        assert_membership(Sip::default());
+        assert_membership(FromBuildHasher(RandomState::new()));
+    }
+
+    #[test]
+    fn sha256_hasher_derives_pair_from_digest() {
+        let mut state = Sha256.build_hasher();
+        std::hash::Hasher::write(&mut state, b"abc");
+        // SHA-256("abc") = ba7816bf8f01cfea 414140de5dae2223 ...
+        assert_eq!(
+            Sha256::finish_pair(&state),
+            (
+                u64::from_le_bytes([0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea]),
+                u64::from_le_bytes([0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23]),
+            )
+        );
+    }
+
+    #[test]
+    fn xxh3_scheme_is_pinned() {
+        // Persisted filters depend on these exact values; they must never change.
+        // XXH128 of the empty input is 99aa06d3014798d8_6001c324468d497f.
+        let state = Xxh3::default().build_hasher();
+        assert_eq!(Xxh3::finish_pair(&state), (0x6001_c324_468d_497f, 0x99aa_06d3_0147_98d8));
+
+        let mut state = Xxh3::default().build_hasher();
+        std::hash::Hasher::write(&mut state, b"bloom");
+        let unseeded = Xxh3::finish_pair(&state);
+        assert_eq!(unseeded, (0x9bed_53e8_ba93_82ec, 0x8948_e6ae_e9a9_6c8a));
+
+        let mut state = Xxh3::with_seed(42).build_hasher();
+        std::hash::Hasher::write(&mut state, b"bloom");
+        assert_ne!(Xxh3::finish_pair(&state), unseeded);
+        assert_eq!(Xxh3::with_seed(42).seed(), 42);
+
+        // Keys spilling over into the streaming state hash the same as one shot.
+        let long = [7u8; 1000];
+        let mut state = Xxh3::with_seed(42).build_hasher();
+        long.chunks(100).for_each(|chunk| std::hash::Hasher::write(&mut state, chunk));
+        assert_eq!(Xxh3::finish_pair(&state), Xxh3::with_seed(42).hash_bytes(&long));
+        assert_eq!(Xxh3::SCHEME, 1);
+    }
+
+    #[test]
+    fn hashed_encoding_works_with_custom_strategy() {
+        let mut bloom_filter: BloomFilter<u64, Hashed, Sha256> =
+            BloomFilter::with_hasher(BloomFilterArgs::default(), Sha256);
+        bloom_filter.insert(&7);
+        assert_eq!(bloom_filter.contains(&7), BloomFilterContainsResponse::Maybe);
+        assert_eq!(bloom_filter.contains(&8), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn hash_indices_spread_a_zero_step() {
+        for h2 in [0, 1024, 3 << 20] {
+            let indices: Vec<usize> = HashIndices::new((5, h2), 4, 1024).collect();
+            assert_eq!(indices, [5, 6, 7, 8]);
+        }
+        let indices: Vec<usize> = HashIndices::new((5, 3), 4, 1024).collect();
+        assert_eq!(indices, [5, 8, 11, 14]);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::BloomHasher;
+use std::hash::{Hash, Hasher};
+
+/// Decides how a value is fed to a filter's hashers.
+pub trait KeyEncoding<T: ?Sized> {
+    /// Writes the value into the given hasher.
+    fn feed<H: Hasher>(value: &T, hasher: &mut H);
+
+    /// Hashes the value into the two base hashes of the given strategy.
+    fn hash_pair<S: BloomHasher>(value: &T, hasher: &S) -> (u64, u64) {
+        let mut state = hasher.build_hasher();
+        Self::feed(value, &mut state);
+        S::finish_pair(&state)
+    }
+}
+
+/// Feeds the raw bytes of values implementing `AsRef<[u8]>` to the hashers.
+/// The result only depends on the bytes themselves, which keeps hashes
+/// stable across languages and Rust versions.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Bytes;
+
+impl<T: AsRef<[u8]> + ?Sized> KeyEncoding<T> for Bytes {
+    fn feed<H: Hasher>(value: &T, hasher: &mut H) {
+        hasher.write(value.as_ref());
+    }
+
+    fn hash_pair<S: BloomHasher>(value: &T, hasher: &S) -> (u64, u64) {
+        hasher.hash_bytes(value.as_ref())
+    }
+}
+
+/// Feeds values implementing `Hash` to the hashers through `Hash::hash`.
+/// Note that the bytes produced by `Hash` implementations are not
+/// guaranteed to be stable across Rust versions.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Hashed;
+
+impl<T: Hash + ?Sized> KeyEncoding<T> for Hashed {
+    fn feed<H: Hasher>(value: &T, hasher: &mut H) {
+        value.hash(hasher);
+    }
+}
@@ -1,29 +1,278 @@
 use bit_vec::BitVec;
-use fnv::FnvHasher;
-use fxhash::FxHasher;
-use std::{marker::PhantomData, collections::hash_map::DefaultHasher, hash::Hasher};
+use hash::HashIndices;
+use std::{
+    borrow::Borrow,
+    fmt,
+    hash::Hash,
+    marker::PhantomData,
+    ops::{BitAnd, BitOr},
+};
 
-pub struct BloomFilter<T> {
+mod atomic;
+mod batch;
+mod blocked;
+mod counting;
+mod cuckoo;
+pub mod format;
+mod hash;
+mod key;
+mod merge;
+mod quotient;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod rng;
+mod scalable;
+pub mod sbbf;
+mod sliding;
+mod stable;
+mod xor;
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+pub use atomic::AtomicBloomFilter;
+pub use blocked::BlockedBloomFilter;
+pub use counting::{CounterWidth, CountingBloomFilter};
+pub use cuckoo::{CuckooFilter, CuckooFilterArgs, CuckooFilterArgsBuilder, CuckooFilterFull};
+pub use format::DecodeError;
+pub use hash::{
+    BloomHasher, Fnv, FnvFx, FnvFxHasher, FromBuildHasher, Fx, PortableHasher, Sha256,
+    Sha256Hasher, Sip, Xxh3, Xxh3Hasher,
+};
+pub use key::{Bytes, Hashed, KeyEncoding};
+pub use merge::IncompatibleError;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapBloomFilter;
+pub use quotient::{
+    QuotientFilter, QuotientFilterArgs, QuotientFilterArgsBuilder, QuotientFilterError,
+};
+pub use sbbf::SbbfBloomFilter;
+pub use scalable::{ScalableBloomFilter, ScalableBloomFilterArgs, ScalableBloomFilterArgsBuilder};
+pub use sliding::{
+    Clock, Rotation, SlidingWindowBloomFilter, SlidingWindowBloomFilterArgs,
+    SlidingWindowBloomFilterArgsBuilder, SystemClock,
+};
+pub use stable::{StableBloomFilter, StableBloomFilterArgs, StableBloomFilterArgsBuilder};
+pub use xor::{
+    BinaryFuseFilter, BinaryFuseFilter16, BinaryFuseFilter8, ConstructionError, Fingerprint,
+    XorFilter, XorFilter16, XorFilter8,
+};
+
+/// A bloom filter over values of type `T`. The encoding `E` decides how
+/// values are fed to the hashers: `Bytes` hashes `AsRef<[u8]>` values by
+/// their bytes, `Hashed` hashes any `Hash` value through `Hash::hash`.
+/// The strategy `S` decides which hash functions are used; the default,
+/// `Xxh3`, is portable across processes and Rust versions.
+pub struct BloomFilter<T, E = Bytes, S = Xxh3> {
     bits: BitVec,
-    _phantom: PhantomData<T>,
+    hashes: usize,
+    hasher: S,
+    _phantom: PhantomData<(T, E)>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct BloomFilterArgs {
     bits: usize,
+    hashes: usize,
 }
 
 impl Default for BloomFilterArgs {
     fn default() -> Self {
-        Self { bits: 1024 }
+        Self { bits: 1024, hashes: 3 }
+    }
+}
+
+impl BloomFilterArgs {
+    /// Creates a builder for BloomFilterArgs.
+    pub fn builder() -> BloomFilterArgsBuilder {
+        BloomFilterArgsBuilder::default()
+    }
+
+    /// The number of bits (m) in the filter.
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// The number of hash functions (k) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+}
+
+/// Builds BloomFilterArgs either from explicit sizes or from the expected
+/// number of items and a target false-positive probability.
+#[derive(Clone, Debug, Default)]
+pub struct BloomFilterArgsBuilder {
+    bits: Option<usize>,
+    hashes: Option<usize>,
+    expected_items: Option<usize>,
+    false_positive_rate: Option<f64>,
+}
+
+impl BloomFilterArgsBuilder {
+    /// Sets the number of bits (m) explicitly.
+    pub fn bits(mut self, bits: usize) -> Self {
+        self.bits = Some(bits);
+        self
+    }
+
+    /// Sets the number of hash functions (k) explicitly.
+    pub fn hashes(mut self, hashes: usize) -> Self {
+        self.hashes = Some(hashes);
+        self
+    }
+
+    /// Sets the number of items (n) the filter is expected to hold.
+    pub fn expected_items(mut self, expected_items: usize) -> Self {
+        self.expected_items = Some(expected_items);
+        self
+    }
+
+    /// Sets the target false-positive probability (p) once `expected_items`
+    /// values have been inserted.
+    pub fn false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    /// Builds the arguments.
+    ///
+    /// When both `expected_items` and `false_positive_rate` are given, the
+    /// optimal sizes are used: m = -n ln(p) / ln(2)^2 and k = (m / n) ln(2).
+    /// Explicitly set `bits` or `hashes` take precedence over the computed
+    /// values. Anything left unset falls back to `BloomFilterArgs::default()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if only one of `expected_items` and `false_positive_rate` is
+    /// set, if `bits`, `hashes` or `expected_items` is zero, or if
+    /// `false_positive_rate` is not strictly between 0 and 1.
+    pub fn build(self) -> BloomFilterArgs {
+        let default = BloomFilterArgs::default();
+        let (optimal_bits, optimal_hashes) = match (self.expected_items, self.false_positive_rate) {
+            (Some(n), Some(p)) => {
+                assert!(n > 0, "expected_items must be greater than zero");
+                assert!(p > 0.0 && p < 1.0, "false_positive_rate must be between 0 and 1");
+                let m = optimal_bits(n, p);
+                (Some(m), Some(optimal_hashes(m, n)))
+            }
+            (Some(_), None) => panic!("expected_items requires a false_positive_rate"),
+            (None, Some(_)) => panic!("false_positive_rate requires expected_items"),
+            (None, None) => (None, None),
+        };
+
+        let bits = self.bits.or(optimal_bits).unwrap_or(default.bits);
+        let hashes = self.hashes.or(optimal_hashes).unwrap_or(default.hashes);
+        assert!(bits > 0, "bits must be greater than zero");
+        assert!(hashes > 0, "hashes must be greater than zero");
+        BloomFilterArgs { bits, hashes }
+    }
+}
+
+/// The optimal number of bits for n items at false-positive probability p.
+fn optimal_bits(n: usize, p: f64) -> usize {
+    let ln2 = std::f64::consts::LN_2;
+    (-(n as f64) * p.ln() / (ln2 * ln2)).ceil().max(1.0) as usize
+}
+
+/// The optimal number of hash functions for m bits and n items.
+fn optimal_hashes(m: usize, n: usize) -> usize {
+    ((m as f64 / n as f64) * std::f64::consts::LN_2).round().max(1.0) as usize
+}
+
+impl<T, E, S: Clone> Clone for BloomFilter<T, E, S> {
+    fn clone(&self) -> Self {
+        Self {
+            bits: self.bits.clone(),
+            hashes: self.hashes,
+            hasher: self.hasher.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for BloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BloomFilterContainsResponse {
     No,
     Maybe
 }
 
+impl BloomFilterContainsResponse {
+    /// Whether the value may have been inserted.
+    pub fn is_maybe(self) -> bool {
+        self == BloomFilterContainsResponse::Maybe
+    }
+
+    /// Whether the value was definitely never inserted.
+    pub fn is_no(self) -> bool {
+        self == BloomFilterContainsResponse::No
+    }
+}
+
+/// Converts "maybe" into `true` and "no" into `false`.
+impl From<BloomFilterContainsResponse> for bool {
+    fn from(response: BloomFilterContainsResponse) -> bool {
+        response.is_maybe()
+    }
+}
+
+/// "Maybe" only if both answers are "maybe", as when a value must be in
+/// two filters.
+impl BitAnd for BloomFilterContainsResponse {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        if self.is_maybe() && other.is_maybe() {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+}
+
+/// "Maybe" if either answer is "maybe", as when a value may be in one of
+/// two filters.
+impl BitOr for BloomFilterContainsResponse {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        if self.is_maybe() || other.is_maybe() {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+}
+
+impl fmt::Display for BloomFilterContainsResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BloomFilterContainsResponse::No => write!(f, "no"),
+            BloomFilterContainsResponse::Maybe => write!(f, "maybe"),
+        }
+    }
+}
+
+/// The details of a `BloomFilter::contains_detailed` lookup, for telling
+/// whether a "maybe" came from a saturated filter or a hash collision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainsDetails {
+    /// The same answer `contains` gives.
+    pub response: BloomFilterContainsResponse,
+    /// The K bit indices computed for the value, in probe order.
+    pub indices: Vec<usize>,
+    /// The position in `indices` of the first unset bit, if any.
+    pub first_miss: Option<usize>,
+    /// The filter's estimated false-positive rate at the time of the lookup.
+    pub estimated_false_positive_rate: f64,
+}
+
 impl<T: AsRef<[u8]>> BloomFilter<T> {
     /// Creates a new BloomFilter with the default arguments.
     pub fn new() -> Self {
@@ -32,14 +281,81 @@ impl<T: AsRef<[u8]>> BloomFilter<T> {
 
     /// Creates a new BloomFilter with the given arguments.
     pub fn with(args: BloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: Hash> BloomFilter<T, Hashed> {
+    /// Creates a new BloomFilter over `Hash` values with the default arguments.
+    pub fn new_hashed() -> Self {
+        BloomFilter::with_hashed(BloomFilterArgs::default())
+    }
+
+    /// Creates a new BloomFilter over `Hash` values with the given arguments.
+    pub fn with_hashed(args: BloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> BloomFilter<T, E, S> {
+    /// Creates a new BloomFilter with the given arguments and hash strategy.
+    pub fn with_hasher(args: BloomFilterArgs, hasher: S) -> Self {
         Self {
             bits: BitVec::from_elem(args.bits, false),
+            hashes: args.hashes,
+            hasher,
             _phantom: PhantomData 
         }
     }
 
+    /// The hash strategy of the BloomFilter.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// The number of bits (m) in the BloomFilter.
+    pub fn bits(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// The number of hash functions (k) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// The number of bits set in the BloomFilter.
+    pub fn count_ones(&self) -> usize {
+        self.bits.blocks().map(|block| block.count_ones() as usize).sum()
+    }
+
+    /// The fraction of bits set in the BloomFilter, between 0 and 1.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bits.len() as f64
+    }
+
+    /// Estimates the number of distinct values inserted from the number of
+    /// set bits X (Swamidass & Baldi): n = -(m / k) ln(1 - X / m).
+    /// Returns infinity once every bit is set.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.bits.len() as f64;
+        -(m / self.hashes as f64) * (1.0 - self.fill_ratio()).ln()
+    }
+
+    /// Estimates the probability that a value which was never inserted is
+    /// reported as "maybe", given the bits set so far: (X / m)^k.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.hashes as i32)
+    }
+
     /// Inserts a new value into the BloomFilter.
-    pub fn insert(&mut self, value: &T) {
+    /// The value may be any borrowed form of `T`, so a `BloomFilter<String>`
+    /// accepts a `&str` without allocating.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
         for idx in self.calculate_hash_indices(value) {
             self.bits.set(idx, true);
         }
@@ -48,7 +364,12 @@ impl<T: AsRef<[u8]>> BloomFilter<T> {
     /// Checks if the BloomFilter contains the given value.
     /// Note that this function returns "no" or "maybe" instead of a boolean.
     /// This is because false positives are possible in a bloom filter.
-    pub fn contains(&self, value: &T) -> BloomFilterContainsResponse {
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
         for idx in self.calculate_hash_indices(value) {
             if !self.bits.get(idx).unwrap_or(false) {
                 return BloomFilterContainsResponse::No;
@@ -57,38 +378,54 @@ impl<T: AsRef<[u8]>> BloomFilter<T> {
         BloomFilterContainsResponse::Maybe
     }
 
+    /// Checks if the BloomFilter contains the given value like `contains`,
+    /// additionally reporting every probed index, the first probe that
+    /// missed, and the current estimated false-positive rate. Unlike
+    /// `contains`, this allocates and always computes all K indices.
+    pub fn contains_detailed<Q>(&self, value: &Q) -> ContainsDetails
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let indices: Vec<usize> = self.calculate_hash_indices(value).collect();
+        let first_miss = indices.iter().position(|&idx| !self.bits.get(idx).unwrap_or(false));
+        ContainsDetails {
+            response: match first_miss {
+                Some(_) => BloomFilterContainsResponse::No,
+                None => BloomFilterContainsResponse::Maybe,
+            },
+            indices,
+            first_miss,
+            estimated_false_positive_rate: self.estimated_false_positive_rate(),
+        }
+    }
+
     /// Calculates the K number of hash values for the given value,
     /// and reduce the hash values modulo the size of the bit vector.
-    fn calculate_hash_indices(&self, value: &T) -> Vec<usize> {
-        let mut fnv = FnvHasher::default();
-        let mut fx = FxHasher::default();
-        let mut default = DefaultHasher::default();
-
-        fnv.write(value.as_ref());
-        fx.write(value.as_ref());
-        default.write(value.as_ref());
-
-        let m = self.bits.len() as u64;
-        vec![
-            (fnv.finish() % m) as usize,
-            (fx.finish() % m) as usize,
-            (default.finish() % m) as usize,
-        ]
+    /// The K hashes are derived from the two base hashes of the strategy.
+    /// Indices are produced lazily, so callers can stop probing early.
+    fn calculate_hash_indices<Q>(&self, value: &Q) -> HashIndices
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        HashIndices::new(E::hash_pair(value, &self.hasher), self.hashes, self.bits.len())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{BloomFilter, BloomFilterContainsResponse};
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, ContainsDetails, Hashed};
 
     #[test]
     fn bloom_filter_does_not_provide_false_negatives() {
         let mut bloom_filter: BloomFilter<String> = BloomFilter::new();
-        let keys = vec!["Test 1", "Other Test", "What about this long one?"];
-        keys.iter().for_each(|&s| bloom_filter.insert(&s.into()));
+        let keys = ["Test 1", "Other Test", "What about this long one?"];
+        keys.iter().for_each(|&s| bloom_filter.insert(s));
         keys.iter().for_each(
             |&s| assert_eq!(
-                bloom_filter.contains(&s.into()),
+                bloom_filter.contains(s),
                 BloomFilterContainsResponse::Maybe
             )
         );
@@ -97,12 +434,181 @@ mod tests {
     #[test]
     fn bloom_filter_empty_provides_no_response() {
         let bloom_filter: BloomFilter<String> = BloomFilter::new();
-        let keys = vec!["This key ain't there", "Testing123", "What about this key right here?"];
+        let keys = ["This key ain't there", "Testing123", "What about this key right here?"];
         keys.iter().for_each(
             |&s| assert_eq!(
-                bloom_filter.contains(&s.into()),
+                bloom_filter.contains(s),
                 BloomFilterContainsResponse::No
             )
         );
     }
+
+    #[test]
+    fn bloom_filter_args_sized_from_expected_items_and_false_positive_rate() {
+        let args = BloomFilterArgs::builder()
+            .expected_items(1_000)
+            .false_positive_rate(0.01)
+            .build();
+        assert_eq!(args.bits(), 9_586);
+        assert_eq!(args.hashes(), 7);
+    }
+
+    #[test]
+    fn bloom_filter_args_explicit_values_take_precedence() {
+        let args = BloomFilterArgs::builder()
+            .expected_items(1_000)
+            .false_positive_rate(0.01)
+            .hashes(4)
+            .build();
+        assert_eq!(args.bits(), 9_586);
+        assert_eq!(args.hashes(), 4);
+        assert_eq!(BloomFilterArgs::builder().bits(64).build().bits(), 64);
+        assert_eq!(BloomFilterArgs::builder().build(), BloomFilterArgs::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "false_positive_rate")]
+    fn bloom_filter_args_rejects_invalid_false_positive_rate() {
+        BloomFilterArgs::builder()
+            .expected_items(10)
+            .false_positive_rate(1.5)
+            .build();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected_items requires a false_positive_rate")]
+    fn bloom_filter_args_rejects_expected_items_alone() {
+        BloomFilterArgs::builder().expected_items(1_000_000).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "false_positive_rate requires expected_items")]
+    fn bloom_filter_args_rejects_false_positive_rate_alone() {
+        BloomFilterArgs::builder().false_positive_rate(0.01).build();
+    }
+
+    #[test]
+    fn bloom_filter_uses_configured_number_of_hashes() {
+        let args = BloomFilterArgs::builder()
+            .expected_items(100)
+            .false_positive_rate(0.0001)
+            .build();
+        let mut bloom_filter: BloomFilter<String> = BloomFilter::with(args);
+        assert_eq!(bloom_filter.hashes(), 13);
+
+        let value = "thirteen probes";
+        bloom_filter.insert(value);
+        assert_eq!(bloom_filter.calculate_hash_indices(value).len(), 13);
+        assert!(bloom_filter.bits.iter().filter(|&bit| bit).count() > 3);
+        assert_eq!(bloom_filter.contains(value), BloomFilterContainsResponse::Maybe);
+    }
+
+    #[test]
+    fn bloom_filter_hashed_accepts_any_hash_type() {
+        #[derive(Hash)]
+        struct Session {
+            tenant: u32,
+            id: (u64, u64),
+        }
+
+        let mut numbers: BloomFilter<u64, Hashed> = BloomFilter::new_hashed();
+        numbers.insert(&42);
+        assert_eq!(numbers.contains(&42), BloomFilterContainsResponse::Maybe);
+        assert_eq!(numbers.contains(&43), BloomFilterContainsResponse::No);
+
+        let mut sessions: BloomFilter<Session, Hashed> = BloomFilter::new_hashed();
+        sessions.insert(&Session { tenant: 7, id: (1, 2) });
+        assert_eq!(
+            sessions.contains(&Session { tenant: 7, id: (1, 2) }),
+            BloomFilterContainsResponse::Maybe
+        );
+        assert_eq!(
+            sessions.contains(&Session { tenant: 8, id: (1, 2) }),
+            BloomFilterContainsResponse::No
+        );
+    }
+
+    #[test]
+    fn bloom_filter_accepts_borrowed_values() {
+        let mut strings: BloomFilter<String> = BloomFilter::new();
+        strings.insert(&String::from("owned"));
+        strings.insert("borrowed");
+        assert_eq!(strings.contains("owned"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(strings.contains(&String::from("borrowed")), BloomFilterContainsResponse::Maybe);
+
+        let mut bytes: BloomFilter<Vec<u8>> = BloomFilter::new();
+        bytes.insert(&[1u8, 2, 3][..]);
+        assert_eq!(bytes.contains(&vec![1u8, 2, 3]), BloomFilterContainsResponse::Maybe);
+
+        let mut hashed: BloomFilter<String, Hashed> = BloomFilter::new_hashed();
+        hashed.insert(&String::from("owned"));
+        assert_eq!(hashed.contains("owned"), BloomFilterContainsResponse::Maybe);
+    }
+
+    #[test]
+    fn bloom_filter_estimates_fill_and_cardinality() {
+        let args = BloomFilterArgs::builder()
+            .expected_items(1_000)
+            .false_positive_rate(0.01)
+            .build();
+        let mut bloom_filter: BloomFilter<u32, Hashed> = BloomFilter::with_hashed(args);
+        assert_eq!(bloom_filter.count_ones(), 0);
+        assert_eq!(bloom_filter.fill_ratio(), 0.0);
+        assert_eq!(bloom_filter.estimated_len(), 0.0);
+        assert_eq!(bloom_filter.estimated_false_positive_rate(), 0.0);
+
+        (0..1_000).for_each(|i| bloom_filter.insert(&i));
+        assert_eq!(bloom_filter.count_ones(), bloom_filter.bits.iter().filter(|&bit| bit).count());
+        assert!((bloom_filter.fill_ratio() - 0.5).abs() < 0.05);
+        assert!((bloom_filter.estimated_len() - 1_000.0).abs() < 50.0);
+        assert!((bloom_filter.estimated_false_positive_rate() - 0.01).abs() < 0.005);
+
+        let mut saturated: BloomFilter<String> =
+            BloomFilter::with(BloomFilterArgs::builder().bits(8).hashes(1).build());
+        (0..100).for_each(|i| saturated.insert(&i.to_string()));
+        assert_eq!(saturated.fill_ratio(), 1.0);
+        assert_eq!(saturated.estimated_len(), f64::INFINITY);
+        assert_eq!(saturated.estimated_false_positive_rate(), 1.0);
+    }
+
+    #[test]
+    fn bloom_filter_contains_detailed_explains_probes() {
+        let mut bloom_filter: BloomFilter<String> =
+            BloomFilter::with(BloomFilterArgs::builder().bits(1_000).hashes(4).build());
+        bloom_filter.insert("present");
+
+        let details = bloom_filter.contains_detailed("present");
+        assert_eq!(details.response, BloomFilterContainsResponse::Maybe);
+        assert_eq!(details.indices, bloom_filter.calculate_hash_indices("present").collect::<Vec<_>>());
+        assert_eq!(details.first_miss, None);
+        assert_eq!(details.estimated_false_positive_rate, bloom_filter.estimated_false_positive_rate());
+
+        let ContainsDetails { response, indices, first_miss, .. } = bloom_filter.contains_detailed("absent");
+        assert_eq!(response, BloomFilterContainsResponse::No);
+        assert_eq!(indices.len(), 4);
+        let first_miss = first_miss.unwrap();
+        assert!(!bloom_filter.bits[indices[first_miss]]);
+        assert!(indices[..first_miss].iter().all(|&idx| bloom_filter.bits[idx]));
+    }
+
+    #[test]
+    fn bloom_filter_contains_response_composes() {
+        use BloomFilterContainsResponse::{Maybe, No};
+
+        assert!(Maybe.is_maybe() && !Maybe.is_no());
+        assert!(No.is_no() && !No.is_maybe());
+        assert!(bool::from(Maybe));
+        assert!(!bool::from(No));
+
+        assert_eq!(Maybe & Maybe, Maybe);
+        assert_eq!(Maybe & No, No);
+        assert_eq!(No & No, No);
+        assert_eq!(Maybe | No, Maybe);
+        assert_eq!(No | No, No);
+
+        assert_eq!(Maybe.to_string(), "maybe");
+        assert_eq!(No.to_string(), "no");
+        let responses: std::collections::HashSet<_> = [Maybe, No, Maybe].into_iter().collect();
+        assert_eq!(responses.len(), 2);
+    }
 }
@@ -0,0 +1,197 @@
+use crate::{BloomFilter, BloomHasher};
+use std::{
+    fmt,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+};
+
+/// An error combining two BloomFilters that were not built with the same
+/// parameters.
+#[derive(Debug, Eq, PartialEq)]
+pub enum IncompatibleError {
+    /// The filters have a different number of bits.
+    Bits { left: usize, right: usize },
+    /// The filters use a different number of hash functions.
+    Hashes { left: usize, right: usize },
+    /// The filters use differently configured hash strategies.
+    Hasher,
+}
+
+impl fmt::Display for IncompatibleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncompatibleError::Bits { left, right } => {
+                write!(f, "bloom filters have different bit counts ({} and {})", left, right)
+            }
+            IncompatibleError::Hashes { left, right } => {
+                write!(f, "bloom filters have different hash counts ({} and {})", left, right)
+            }
+            IncompatibleError::Hasher => write!(f, "bloom filters use different hash strategies"),
+        }
+    }
+}
+
+impl std::error::Error for IncompatibleError {}
+
+impl<T, E, S: BloomHasher + PartialEq> BloomFilter<T, E, S> {
+    /// Adds every value of `other` to this BloomFilter. Afterwards it answers
+    /// "maybe" for any value either filter did.
+    pub fn union_with(&mut self, other: &Self) -> Result<(), IncompatibleError> {
+        self.check_compatible(other)?;
+        self.bits.or(&other.bits);
+        Ok(())
+    }
+
+    /// Keeps only the bits set in both BloomFilters. Afterwards it answers
+    /// "maybe" for every value both filters did, and possibly for values
+    /// only one of them held, so the false-positive rate may be higher than
+    /// that of a filter built from the intersection directly.
+    pub fn intersect_with(&mut self, other: &Self) -> Result<(), IncompatibleError> {
+        self.check_compatible(other)?;
+        self.bits.and(&other.bits);
+        Ok(())
+    }
+
+    /// Checks that both BloomFilters share bit length, hash count and hash
+    /// strategy, so their bits can be combined.
+    pub fn check_compatible(&self, other: &Self) -> Result<(), IncompatibleError> {
+        if self.bits.len() != other.bits.len() {
+            return Err(IncompatibleError::Bits { left: self.bits.len(), right: other.bits.len() });
+        }
+        if self.hashes != other.hashes {
+            return Err(IncompatibleError::Hashes { left: self.hashes, right: other.hashes });
+        }
+        if self.hasher != other.hasher {
+            return Err(IncompatibleError::Hasher);
+        }
+        Ok(())
+    }
+}
+
+impl<T, E, S: BloomHasher + PartialEq + Clone> BloomFilter<T, E, S> {
+    /// Returns a BloomFilter holding the values of both filters.
+    pub fn union(&self, other: &Self) -> Result<Self, IncompatibleError> {
+        let mut result = self.clone();
+        result.union_with(other)?;
+        Ok(result)
+    }
+
+    /// Returns a BloomFilter holding the bits set in both filters.
+    /// See `intersect_with`.
+    pub fn intersect(&self, other: &Self) -> Result<Self, IncompatibleError> {
+        let mut result = self.clone();
+        result.intersect_with(other)?;
+        Ok(result)
+    }
+}
+
+/// # Panics
+///
+/// Panics if the filters are incompatible; use `union` to handle that case.
+impl<T, E, S: BloomHasher + PartialEq + Clone> BitOr for &BloomFilter<T, E, S> {
+    type Output = BloomFilter<T, E, S>;
+
+    fn bitor(self, other: Self) -> BloomFilter<T, E, S> {
+        self.union(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// # Panics
+///
+/// Panics if the filters are incompatible; use `intersect` to handle that
+/// case.
+impl<T, E, S: BloomHasher + PartialEq + Clone> BitAnd for &BloomFilter<T, E, S> {
+    type Output = BloomFilter<T, E, S>;
+
+    fn bitand(self, other: Self) -> BloomFilter<T, E, S> {
+        self.intersect(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// # Panics
+///
+/// Panics if the filters are incompatible; use `union_with` to handle that
+/// case.
+impl<T, E, S: BloomHasher + PartialEq> BitOrAssign<&BloomFilter<T, E, S>> for BloomFilter<T, E, S> {
+    fn bitor_assign(&mut self, other: &Self) {
+        self.union_with(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// # Panics
+///
+/// Panics if the filters are incompatible; use `intersect_with` to handle
+/// that case.
+impl<T, E, S: BloomHasher + PartialEq> BitAndAssign<&BloomFilter<T, E, S>> for BloomFilter<T, E, S> {
+    fn bitand_assign(&mut self, other: &Self) {
+        self.intersect_with(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IncompatibleError;
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, Bytes, PortableHasher, Xxh3};
+
+    fn shard(keys: &[&str]) -> BloomFilter<String> {
+        let mut bloom_filter = BloomFilter::new();
+        keys.iter().for_each(|&key| bloom_filter.insert(key));
+        bloom_filter
+    }
+
+    #[test]
+    fn bloom_filter_union_contains_both_shards() {
+        let left = shard(&["a", "b"]);
+        let right = shard(&["c"]);
+        let union = left.union(&right).unwrap();
+        for key in ["a", "b", "c"] {
+            assert_eq!(union.contains(key), BloomFilterContainsResponse::Maybe);
+        }
+        assert_eq!(union.contains("d"), BloomFilterContainsResponse::No);
+
+        let mut merged = shard(&["a", "b"]);
+        merged |= &right;
+        assert_eq!(merged.to_bytes(), union.to_bytes());
+        assert_eq!((&left | &right).to_bytes(), union.to_bytes());
+    }
+
+    #[test]
+    fn bloom_filter_intersection_keeps_shared_values() {
+        let left = shard(&["a", "b"]);
+        let right = shard(&["b", "c"]);
+        let intersection = left.intersect(&right).unwrap();
+        assert_eq!(intersection.contains("b"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(intersection.contains("a"), BloomFilterContainsResponse::No);
+        assert_eq!(intersection.contains("c"), BloomFilterContainsResponse::No);
+
+        let mut merged = shard(&["a", "b"]);
+        merged &= &right;
+        assert_eq!(merged.to_bytes(), intersection.to_bytes());
+        assert_eq!((&left & &right).to_bytes(), intersection.to_bytes());
+    }
+
+    #[test]
+    fn bloom_filter_merge_rejects_incompatible_filters() {
+        let filter = |bits, hashes, seed| -> BloomFilter<String, Bytes, Xxh3> {
+            let args = BloomFilterArgs::builder().bits(bits).hashes(hashes).build();
+            BloomFilter::with_hasher(args, Xxh3::with_seed(seed))
+        };
+        let mut base = filter(64, 3, 0);
+        assert_eq!(
+            base.union_with(&filter(128, 3, 0)),
+            Err(IncompatibleError::Bits { left: 64, right: 128 })
+        );
+        assert_eq!(
+            base.intersect_with(&filter(64, 4, 0)),
+            Err(IncompatibleError::Hashes { left: 3, right: 4 })
+        );
+        assert_eq!(base.union(&filter(64, 3, 1)).err(), Some(IncompatibleError::Hasher));
+    }
+
+    #[test]
+    #[should_panic(expected = "different bit counts")]
+    fn bloom_filter_operator_panics_on_incompatible_filters() {
+        let left: BloomFilter<String> = BloomFilter::new();
+        let right: BloomFilter<String> = BloomFilter::with(BloomFilterArgs::builder().bits(8).build());
+        let _ = &left | &right;
+    }
+}
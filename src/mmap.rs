@@ -0,0 +1,183 @@
+//! Read-only BloomFilters over memory-mapped files, enabled by the `mmap`
+//! feature.
+
+use crate::{
+    format::{DecodeError, Header, CHECKSUM_LEN, HEADER_LEN},
+    hash::HashIndices,
+    BloomFilterContainsResponse, Bytes, KeyEncoding, PortableHasher, Xxh3,
+};
+use memmap2::Mmap;
+use std::{borrow::Borrow, fs::File, marker::PhantomData, path::Path};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// A read-only BloomFilter answering queries directly against a file in
+/// the binary format of the `format` module, mapped into memory. Opening
+/// the file only reads its header; pages of the bit array are loaded by the
+/// operating system as lookups touch them.
+///
+/// The file must not be modified or truncated while it is mapped.
+pub struct MmapBloomFilter<T, E = Bytes, S = Xxh3> {
+    mmap: Mmap,
+    header: Header,
+    hasher: S,
+    _phantom: PhantomData<(T, E)>,
+}
+
+impl<T, E, S: PortableHasher> MmapBloomFilter<T, E, S> {
+    /// Maps the filter in the file at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecodeError> {
+        Self::from_file(&File::open(path)?)
+    }
+
+    /// Maps the filter in the given file, validating its header and
+    /// length. The checksum is not verified, since that reads the whole
+    /// file; call `verify_checksum` to do so.
+    pub fn from_file(file: &File) -> Result<Self, DecodeError> {
+        // SAFETY: the mapping is read-only, and the type documents that the
+        // file must not be modified while mapped.
+        let mmap = unsafe { Mmap::map(file)? };
+
+        let header: &[u8; HEADER_LEN] = mmap
+            .get(..HEADER_LEN)
+            .ok_or(DecodeError::Truncated)?
+            .try_into()
+            .unwrap();
+        let header = Header::decode(header)?;
+        header.check_scheme::<S>()?;
+
+        let len = HEADER_LEN + header.payload_len() + CHECKSUM_LEN;
+        if mmap.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+        if mmap.len() > len {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(Self { mmap, header, hasher: S::with_seed(header.seed), _phantom: PhantomData })
+    }
+
+    /// Reads the whole file and checks it against its checksum.
+    pub fn verify_checksum(&self) -> Result<(), DecodeError> {
+        let (contents, checksum) = self.mmap.split_at(self.mmap.len() - CHECKSUM_LEN);
+        if xxh3_64(contents) != u64::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        Ok(())
+    }
+
+    /// The number of bits (m) in the filter.
+    pub fn bits(&self) -> usize {
+        self.header.bits
+    }
+
+    /// The number of hash functions (k) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.header.hashes
+    }
+
+    /// The hash strategy of the filter.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Checks if the filter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let payload = &self.mmap[HEADER_LEN..HEADER_LEN + self.header.payload_len()];
+        let indices =
+            HashIndices::new(E::hash_pair(value, &self.hasher), self.header.hashes, self.header.bits);
+        for idx in indices {
+            if payload[idx / 8] & (0x80 >> (idx % 8)) == 0 {
+                return BloomFilterContainsResponse::No;
+            }
+        }
+        BloomFilterContainsResponse::Maybe
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MmapBloomFilter;
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, DecodeError, Sha256};
+    use std::io::Write;
+
+    fn write_filter(bytes: &[u8]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn mmap_bloom_filter_answers_like_bloom_filter() {
+        let args = BloomFilterArgs::builder().expected_items(1_000).false_positive_rate(0.01).build();
+        let mut bloom_filter: BloomFilter<String> = BloomFilter::with(args);
+        (0..1_000).for_each(|i| bloom_filter.insert(&i.to_string()));
+        let file = write_filter(&bloom_filter.to_bytes());
+
+        let mapped: MmapBloomFilter<String> = MmapBloomFilter::open(file.path()).unwrap();
+        mapped.verify_checksum().unwrap();
+        assert_eq!(mapped.bits(), bloom_filter.bits());
+        assert_eq!(mapped.hashes(), bloom_filter.hashes());
+        for i in 0..5_000 {
+            let key = i.to_string();
+            assert_eq!(mapped.contains(key.as_str()), bloom_filter.contains(key.as_str()));
+        }
+        assert_eq!(mapped.contains("0"), BloomFilterContainsResponse::Maybe);
+    }
+
+    #[test]
+    fn mmap_bloom_filter_validates_file() {
+        let bloom_filter: BloomFilter<String> = BloomFilter::new();
+        let bytes = bloom_filter.to_bytes();
+
+        let file = write_filter(&bytes[..bytes.len() - 1]);
+        assert!(matches!(
+            MmapBloomFilter::<String>::open(file.path()),
+            Err(DecodeError::Truncated)
+        ));
+
+        let file = write_filter(&bytes);
+        assert!(matches!(
+            MmapBloomFilter::<String, crate::Bytes, UnknownScheme>::open(file.path()),
+            Err(DecodeError::SchemeMismatch { expected: 99, found: 1 })
+        ));
+
+        let mut corrupted = bytes.clone();
+        corrupted[40] ^= 1;
+        let file = write_filter(&corrupted);
+        let mapped = MmapBloomFilter::<String>::open(file.path()).unwrap();
+        assert!(matches!(mapped.verify_checksum(), Err(DecodeError::ChecksumMismatch)));
+    }
+
+    /// A portable strategy with a scheme id no shipped strategy uses.
+    #[derive(Default)]
+    struct UnknownScheme;
+
+    impl crate::BloomHasher for UnknownScheme {
+        type Hasher = <Sha256 as crate::BloomHasher>::Hasher;
+
+        fn build_hasher(&self) -> Self::Hasher {
+            Sha256.build_hasher()
+        }
+
+        fn finish_pair(hasher: &Self::Hasher) -> (u64, u64) {
+            Sha256::finish_pair(hasher)
+        }
+    }
+
+    impl crate::PortableHasher for UnknownScheme {
+        const SCHEME: u8 = 99;
+
+        fn with_seed(_: u64) -> Self {
+            UnknownScheme
+        }
+
+        fn seed(&self) -> u64 {
+            0
+        }
+    }
+}
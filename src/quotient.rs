@@ -0,0 +1,987 @@
+use crate::{BloomFilterContainsResponse, BloomHasher, Bytes, Hashed, KeyEncoding, Xxh3};
+use bit_vec::BitVec;
+use std::{borrow::Borrow, fmt, hash::Hash, marker::PhantomData};
+
+/// The load that `QuotientFilterArgsBuilder` and `merge` size tables for.
+const MAX_MERGE_LOAD: f64 = 0.75;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuotientFilterArgs {
+    quotient_bits: u32,
+    remainder_bits: u32,
+}
+
+impl Default for QuotientFilterArgs {
+    fn default() -> Self {
+        Self { quotient_bits: 10, remainder_bits: 8 }
+    }
+}
+
+impl QuotientFilterArgs {
+    /// Creates a builder for QuotientFilterArgs.
+    pub fn builder() -> QuotientFilterArgsBuilder {
+        QuotientFilterArgsBuilder::default()
+    }
+
+    /// The number of fingerprint bits selecting a slot (q). The filter has
+    /// 2^q slots.
+    pub fn quotient_bits(&self) -> u32 {
+        self.quotient_bits
+    }
+
+    /// The number of fingerprint bits stored in a slot (r).
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+}
+
+/// Builds QuotientFilterArgs either from explicit bit counts or from the
+/// expected number of items and a target false-positive probability.
+#[derive(Clone, Debug, Default)]
+pub struct QuotientFilterArgsBuilder {
+    quotient_bits: Option<u32>,
+    remainder_bits: Option<u32>,
+    expected_items: Option<usize>,
+    false_positive_rate: Option<f64>,
+}
+
+impl QuotientFilterArgsBuilder {
+    /// Sets the number of quotient bits (q) explicitly.
+    pub fn quotient_bits(mut self, quotient_bits: u32) -> Self {
+        self.quotient_bits = Some(quotient_bits);
+        self
+    }
+
+    /// Sets the number of remainder bits (r) explicitly.
+    pub fn remainder_bits(mut self, remainder_bits: u32) -> Self {
+        self.remainder_bits = Some(remainder_bits);
+        self
+    }
+
+    /// Sets the number of items (n) the filter is expected to hold.
+    pub fn expected_items(mut self, expected_items: usize) -> Self {
+        self.expected_items = Some(expected_items);
+        self
+    }
+
+    /// Sets the target false-positive probability (p) once `expected_items`
+    /// values have been inserted.
+    pub fn false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    /// Builds the arguments.
+    ///
+    /// When both `expected_items` and `false_positive_rate` are given, the
+    /// table is sized for a load of at most 75%, q = log2(n / 0.75), and
+    /// r = log2(1 / p), both rounded up. Explicitly set `quotient_bits` or
+    /// `remainder_bits` take precedence over the computed values. Anything
+    /// left unset falls back to `QuotientFilterArgs::default()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if only one of `expected_items` and `false_positive_rate` is
+    /// set, if `quotient_bits` is not between 1 and 40, `remainder_bits` is
+    /// zero, their sum exceeds 64, `expected_items` is zero, or
+    /// `false_positive_rate` is not strictly between 0 and 1.
+    pub fn build(self) -> QuotientFilterArgs {
+        let default = QuotientFilterArgs::default();
+        let (optimal_quotient, optimal_remainder) =
+            match (self.expected_items, self.false_positive_rate) {
+                (Some(n), Some(p)) => {
+                    assert!(n > 0, "expected_items must be greater than zero");
+                    assert!(p > 0.0 && p < 1.0, "false_positive_rate must be between 0 and 1");
+                    let q = (n as f64 / MAX_MERGE_LOAD).log2().ceil().max(1.0) as u32;
+                    let r = (1.0 / p).log2().ceil().max(1.0) as u32;
+                    (Some(q), Some(r))
+                }
+                (Some(_), None) => panic!("expected_items requires a false_positive_rate"),
+                (None, Some(_)) => panic!("false_positive_rate requires expected_items"),
+                (None, None) => (None, None),
+            };
+
+        let quotient_bits =
+            self.quotient_bits.or(optimal_quotient).unwrap_or(default.quotient_bits);
+        let remainder_bits =
+            self.remainder_bits.or(optimal_remainder).unwrap_or(default.remainder_bits);
+        assert!((1..=40).contains(&quotient_bits), "quotient_bits must be between 1 and 40");
+        assert!(remainder_bits > 0, "remainder_bits must be greater than zero");
+        assert!(quotient_bits + remainder_bits <= 64, "fingerprints are at most 64 bits");
+        QuotientFilterArgs { quotient_bits, remainder_bits }
+    }
+}
+
+/// An error from a QuotientFilter operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QuotientFilterError {
+    /// Every slot but one is in use. Call `double` to make room.
+    Full,
+    /// The filter cannot double again: only one remainder bit is left.
+    RemainderExhausted,
+    /// The filters hash values to fingerprints of different lengths.
+    FingerprintBits { left: u32, right: u32 },
+    /// The filters use differently configured hash strategies.
+    Hasher,
+}
+
+impl fmt::Display for QuotientFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotientFilterError::Full => write!(f, "quotient filter is full"),
+            QuotientFilterError::RemainderExhausted => {
+                write!(f, "quotient filter has no remainder bits left to double")
+            }
+            QuotientFilterError::FingerprintBits { left, right } => write!(
+                f,
+                "quotient filters have different fingerprint lengths ({} and {})",
+                left, right
+            ),
+            QuotientFilterError::Hasher => {
+                write!(f, "quotient filters use different hash strategies")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuotientFilterError {}
+
+/// A quotient filter (Bender et al., "Don't Thrash: How to Cache Your
+/// Hash on Flash"). Each value is hashed to a fingerprint of q + r bits;
+/// the top q bits pick a slot and the low r bits are stored, in sorted
+/// runs kept in place by linear probing.
+///
+/// Since the fingerprints can be recovered from the table, the filter can
+/// double its slots or be merged with another one without the original
+/// values.
+pub struct QuotientFilter<T, E = Bytes, S = Xxh3> {
+    table: Table,
+    hasher: S,
+    _phantom: PhantomData<(T, E)>,
+}
+
+impl<T: AsRef<[u8]>> QuotientFilter<T> {
+    /// Creates a new QuotientFilter with the default arguments.
+    pub fn new() -> Self {
+        QuotientFilter::with(QuotientFilterArgs::default())
+    }
+
+    /// Creates a new QuotientFilter with the given arguments.
+    pub fn with(args: QuotientFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for QuotientFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> QuotientFilter<T, Hashed> {
+    /// Creates a new QuotientFilter over `Hash` values with the default
+    /// arguments.
+    pub fn new_hashed() -> Self {
+        QuotientFilter::with_hashed(QuotientFilterArgs::default())
+    }
+
+    /// Creates a new QuotientFilter over `Hash` values with the given
+    /// arguments.
+    pub fn with_hashed(args: QuotientFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> QuotientFilter<T, E, S> {
+    /// Creates a new QuotientFilter with the given arguments and hash
+    /// strategy.
+    pub fn with_hasher(args: QuotientFilterArgs, hasher: S) -> Self {
+        Self {
+            table: Table::new(args.quotient_bits, args.remainder_bits),
+            hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The hash strategy of the QuotientFilter.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// The number of quotient bits (q).
+    pub fn quotient_bits(&self) -> u32 {
+        self.table.quotient_bits
+    }
+
+    /// The number of remainder bits (r).
+    pub fn remainder_bits(&self) -> u32 {
+        self.table.remainder_bits
+    }
+
+    /// The number of slots, 2^q.
+    pub fn slots(&self) -> usize {
+        self.table.slots()
+    }
+
+    /// The number of fingerprints stored.
+    pub fn len(&self) -> usize {
+        self.table.len
+    }
+
+    /// Whether no fingerprints are stored.
+    pub fn is_empty(&self) -> bool {
+        self.table.len == 0
+    }
+
+    /// The fraction of slots in use. Lookups slow down noticeably past
+    /// about 0.9.
+    pub fn load_factor(&self) -> f64 {
+        self.table.len as f64 / self.slots() as f64
+    }
+
+    /// The expected false-positive rate at the current load,
+    /// 1 - e^(-load / 2^r).
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        let per_remainder = (-(self.remainder_bits() as f64)).exp2();
+        1.0 - (-self.load_factor() * per_remainder).exp()
+    }
+
+    /// Inserts a new value into the QuotientFilter. Inserting a value twice
+    /// stores its fingerprint twice, so it takes two removes to drop it.
+    pub fn insert<Q>(&mut self, value: &Q) -> Result<(), QuotientFilterError>
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let fingerprint = self.fingerprint(value);
+        self.table.insert(fingerprint)
+    }
+
+    /// Removes one copy of a value from the QuotientFilter. Returns "no" and
+    /// leaves the filter untouched if the value was definitely not present.
+    ///
+    /// Only remove values that were inserted: removing a false positive
+    /// deletes the fingerprint of another value.
+    pub fn remove<Q>(&mut self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let fingerprint = self.fingerprint(value);
+        if self.table.remove(fingerprint) {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+
+    /// Checks if the QuotientFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        if self.table.contains(self.fingerprint(value)) {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+
+    /// Doubles the number of slots in place by moving one remainder bit
+    /// into the quotient. Every value the filter held is still reported as
+    /// present, and the original values are not needed. The load halves
+    /// while the false-positive rate per fingerprint doubles.
+    pub fn double(&mut self) -> Result<(), QuotientFilterError> {
+        if self.table.remainder_bits == 1 {
+            return Err(QuotientFilterError::RemainderExhausted);
+        }
+        self.table.double();
+        Ok(())
+    }
+
+    /// The fingerprint of a value: the top q + r bits of h1.
+    fn fingerprint<Q>(&self, value: &Q) -> u64
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let (h1, _) = E::hash_pair(value, &self.hasher);
+        h1 >> (64 - self.table.fingerprint_bits())
+    }
+}
+
+impl<T, E, S: BloomHasher + Clone + PartialEq> QuotientFilter<T, E, S> {
+    /// Merges two QuotientFilters into a new one holding the fingerprints of
+    /// both, with at least as many slots as the larger input, doubled until
+    /// it is at most 75% full.
+    ///
+    /// Both tables are walked in quotient order, which yields their
+    /// fingerprints sorted, and the combined stream is written to the output
+    /// slots front to back in a single pass.
+    pub fn merge(&self, other: &Self) -> Result<Self, QuotientFilterError> {
+        let (left, right) = (self.table.fingerprint_bits(), other.table.fingerprint_bits());
+        if left != right {
+            return Err(QuotientFilterError::FingerprintBits { left, right });
+        }
+        if self.hasher != other.hasher {
+            return Err(QuotientFilterError::Hasher);
+        }
+
+        let len = self.table.len + other.table.len;
+        let mut quotient_bits = self.table.quotient_bits.max(other.table.quotient_bits);
+        while quotient_bits + 1 < left
+            && len as f64 > MAX_MERGE_LOAD * (1usize << quotient_bits) as f64
+        {
+            quotient_bits += 1;
+        }
+        if len + 1 >= 1 << quotient_bits {
+            return Err(QuotientFilterError::Full);
+        }
+
+        let (mut ours, mut theirs) =
+            (self.table.fingerprints().peekable(), other.table.fingerprints().peekable());
+        let merged = std::iter::from_fn(|| match (ours.peek(), theirs.peek()) {
+            (Some(a), Some(b)) if b < a => theirs.next(),
+            (Some(_), _) => ours.next(),
+            (None, _) => theirs.next(),
+        });
+        let mut table = Table::new(quotient_bits, left - quotient_bits);
+        table.append_sorted(merged);
+        Ok(Self { table, hasher: self.hasher.clone(), _phantom: PhantomData })
+    }
+}
+
+/// The slots of a quotient filter, working on fingerprints.
+///
+/// Each slot has three flags: `occupied` marks that some fingerprint has
+/// this slot as its quotient, `continuation` that the slot continues the
+/// run of the slot before it, and `shifted` that the slot holds a
+/// remainder whose quotient is an earlier slot. A slot with none of them
+/// set is empty. Runs are kept sorted by remainder, so walking them in
+/// quotient order yields the fingerprints sorted.
+struct Table {
+    occupied: BitVec,
+    continuation: BitVec,
+    shifted: BitVec,
+    /// `remainder_bits`-wide remainders packed back to back.
+    remainders: Vec<u64>,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    len: usize,
+}
+
+impl Table {
+    fn new(quotient_bits: u32, remainder_bits: u32) -> Self {
+        let slots = 1usize << quotient_bits;
+        Self {
+            occupied: BitVec::from_elem(slots, false),
+            continuation: BitVec::from_elem(slots, false),
+            shifted: BitVec::from_elem(slots, false),
+            remainders: vec![0; (slots * remainder_bits as usize).div_ceil(64)],
+            quotient_bits,
+            remainder_bits,
+            len: 0,
+        }
+    }
+
+    fn slots(&self) -> usize {
+        self.occupied.len()
+    }
+
+    fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits + self.remainder_bits
+    }
+
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        let quotient = (fingerprint >> self.remainder_bits) as usize & (self.slots() - 1);
+        (quotient, fingerprint & self.remainder_mask())
+    }
+
+    fn remainder_mask(&self) -> u64 {
+        (1 << self.remainder_bits) - 1
+    }
+
+    fn next(&self, slot: usize) -> usize {
+        (slot + 1) & (self.slots() - 1)
+    }
+
+    fn prev(&self, slot: usize) -> usize {
+        slot.wrapping_sub(1) & (self.slots() - 1)
+    }
+
+    fn is_empty_slot(&self, slot: usize) -> bool {
+        !self.occupied[slot] && !self.continuation[slot] && !self.shifted[slot]
+    }
+
+    fn remainder(&self, slot: usize) -> u64 {
+        self.packed(slot, self.remainder_bits)
+    }
+
+    fn set_remainder(&mut self, slot: usize, value: u64) {
+        self.set_packed(slot, self.remainder_bits, value);
+    }
+
+    /// Reads the `bits`-wide field at index `slot` of the remainders.
+    fn packed(&self, slot: usize, bits: u32) -> u64 {
+        let mask = (1 << bits) - 1;
+        let bit = slot * bits as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.remainders[word] >> offset;
+        if offset + bits as usize > 64 {
+            value |= self.remainders[word + 1] << (64 - offset);
+        }
+        value & mask
+    }
+
+    fn set_packed(&mut self, slot: usize, bits: u32, value: u64) {
+        let mask = (1 << bits) - 1;
+        let bit = slot * bits as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        self.remainders[word] = (self.remainders[word] & !(mask << offset)) | (value << offset);
+        if offset + bits as usize > 64 {
+            let spill = 64 - offset;
+            self.remainders[word + 1] =
+                (self.remainders[word + 1] & !(mask >> spill)) | (value >> spill);
+        }
+    }
+
+    fn set_flags(&mut self, slot: usize, continuation: bool, shifted: bool) {
+        self.continuation.set(slot, continuation);
+        self.shifted.set(slot, shifted);
+    }
+
+    /// The first slot of the cluster containing the given non-empty slot.
+    fn cluster_start(&self, mut slot: usize) -> usize {
+        while self.shifted[slot] {
+            slot = self.prev(slot);
+        }
+        slot
+    }
+
+    /// The slot where the run of an occupied quotient starts, walking the
+    /// runs of its cluster in step with the occupied quotients. For a
+    /// quotient just marked occupied, where its run belongs.
+    fn run_start(&self, quotient: usize) -> usize {
+        let mut canonical = self.cluster_start(quotient);
+        let mut slot = canonical;
+        while canonical != quotient {
+            loop {
+                slot = self.next(slot);
+                if !self.continuation[slot] {
+                    break;
+                }
+            }
+            loop {
+                canonical = self.next(canonical);
+                if self.occupied[canonical] {
+                    break;
+                }
+            }
+        }
+        slot
+    }
+
+    fn insert(&mut self, fingerprint: u64) -> Result<(), QuotientFilterError> {
+        if self.len + 1 >= self.slots() {
+            return Err(QuotientFilterError::Full);
+        }
+        let (quotient, remainder) = self.split(fingerprint);
+        if self.is_empty_slot(quotient) {
+            self.occupied.set(quotient, true);
+            self.set_remainder(quotient, remainder);
+            self.len += 1;
+            return Ok(());
+        }
+
+        let existing_run = self.occupied[quotient];
+        self.occupied.set(quotient, true);
+        let start = self.run_start(quotient);
+        let mut slot = start;
+        if existing_run {
+            while self.remainder(slot) < remainder {
+                slot = self.next(slot);
+                if !self.continuation[slot] {
+                    break;
+                }
+            }
+        }
+
+        // Everything from `slot` up to the next empty slot moves one slot
+        // right. A displaced run head becomes a continuation of its run.
+        let (mut remainder, mut continuation, mut shifted) =
+            (remainder, slot != start, slot != quotient);
+        let mut head_displaced = existing_run && slot == start;
+        loop {
+            let empty = self.is_empty_slot(slot);
+            let displaced = (self.remainder(slot), self.continuation[slot]);
+            self.set_remainder(slot, remainder);
+            self.set_flags(slot, continuation, shifted);
+            if empty {
+                break;
+            }
+            (remainder, continuation, shifted) = (displaced.0, displaced.1 || head_displaced, true);
+            head_displaced = false;
+            slot = self.next(slot);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if !self.occupied[quotient] {
+            return false;
+        }
+        let start = self.run_start(quotient);
+        let mut slot = start;
+        while self.remainder(slot) != remainder {
+            slot = self.next(slot);
+            if !self.continuation[slot] {
+                return false;
+            }
+        }
+
+        let run_continues = self.continuation[self.next(slot)];
+        if slot == start && !run_continues {
+            self.occupied.set(quotient, false);
+        }
+
+        // Shifted slots after the hole move one slot left, up to the next
+        // empty slot or cluster start. A run head moved back onto its own
+        // quotient is no longer shifted.
+        let mut canonical = quotient;
+        let mut promote = slot == start && run_continues;
+        loop {
+            let next = self.next(slot);
+            if !self.shifted[next] {
+                self.set_flags(slot, false, false);
+                break;
+            }
+            let mut continuation = self.continuation[next];
+            if promote {
+                continuation = false;
+                promote = false;
+            } else if !continuation {
+                loop {
+                    canonical = self.next(canonical);
+                    if self.occupied[canonical] {
+                        break;
+                    }
+                }
+            }
+            let moved = self.remainder(next);
+            self.set_remainder(slot, moved);
+            self.set_flags(slot, continuation, slot != canonical);
+            slot = next;
+        }
+        self.len -= 1;
+        true
+    }
+
+    fn contains(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if !self.occupied[quotient] {
+            return false;
+        }
+        let mut slot = self.run_start(quotient);
+        loop {
+            if self.remainder(slot) == remainder {
+                return true;
+            }
+            slot = self.next(slot);
+            if !self.continuation[slot] {
+                return false;
+            }
+        }
+    }
+
+    /// The stored fingerprints in increasing order.
+    fn fingerprints(&self) -> Fingerprints<'_> {
+        let quotient = (0..self.slots()).find(|&slot| self.occupied[slot]).unwrap_or(0);
+        let slot = match self.len {
+            0 => 0,
+            _ => quotient + (self.run_start(quotient).wrapping_sub(quotient) & (self.slots() - 1)),
+        };
+        Fingerprints { table: self, quotient, slot, remaining: self.len }
+    }
+
+    /// Writes fingerprints given in increasing order into an empty table,
+    /// front to back: each goes to its quotient or the slot after the
+    /// previous one, whichever is later. Only a final cluster running past
+    /// the last slot falls back to `insert` to wrap around.
+    fn append_sorted(&mut self, fingerprints: impl Iterator<Item = u64>) {
+        let mut writer = Writer::default();
+        for fingerprint in fingerprints {
+            let (quotient, remainder) = self.split(fingerprint);
+            if !writer.append(self, quotient, remainder) {
+                self.insert(fingerprint).expect("table has room");
+            }
+        }
+    }
+
+    /// Doubles the slots in place, moving the top remainder bit into the
+    /// quotient. The table is grown and rewritten in two sequential passes:
+    /// a backward pass spreads every slot apart to twice its index, then a
+    /// forward pass lays the fingerprints out under the new quotients. Only
+    /// a cluster wrapping from the last slot around to the first is set
+    /// aside and re-inserted afterwards.
+    fn double(&mut self) {
+        let wrapped = self.take_wrapped_cluster();
+        let (old_slots, old_bits) = (self.slots(), self.remainder_bits);
+        self.quotient_bits += 1;
+        self.remainder_bits -= 1;
+        for flags in [&mut self.occupied, &mut self.continuation, &mut self.shifted] {
+            flags.grow(old_slots, false);
+        }
+        let words = (2 * old_slots * self.remainder_bits as usize).div_ceil(64);
+        self.remainders.resize(words, 0);
+
+        // Spread slot i to slot 2i, with the top remainder bit in the field
+        // of slot 2i + 1. New fields start at or after the end of old slot
+        // i - 1, so going backwards never overwrites an unread slot.
+        for slot in (0..old_slots).rev() {
+            let flags = (self.occupied[slot], self.continuation[slot], self.shifted[slot]);
+            let remainder = self.packed(slot, old_bits);
+            self.occupied.set(slot, false);
+            self.set_flags(slot, false, false);
+            self.occupied.set(2 * slot + 1, false);
+            self.set_flags(2 * slot + 1, false, false);
+            self.occupied.set(2 * slot, flags.0);
+            self.set_flags(2 * slot, flags.1, flags.2);
+            self.set_remainder(2 * slot, remainder & self.remainder_mask());
+            self.set_remainder(2 * slot + 1, remainder >> self.remainder_bits);
+        }
+
+        // Read the spread slots front to back and write each fingerprint
+        // under its new quotient, recounting them. Without wrapping, a
+        // fingerprint lands before the spread copy of the next one, so
+        // nothing unread is overwritten.
+        self.len = 0;
+        let mut writer = Writer::default();
+        let mut quotient = None;
+        for slot in (0..old_slots).map(|slot| 2 * slot) {
+            let (continuation, shifted) = (self.continuation[slot], self.shifted[slot]);
+            if !self.occupied[slot] && !continuation && !shifted {
+                continue;
+            }
+            self.set_flags(slot, false, false);
+            if !continuation {
+                let mut next = quotient.map_or(0, |quotient| quotient + 1);
+                while !self.occupied[2 * next] {
+                    next += 1;
+                }
+                self.occupied.set(2 * next, false);
+                quotient = Some(next);
+            }
+            let high = self.remainder(slot + 1) as usize;
+            let remainder = self.remainder(slot);
+            let quotient = 2 * quotient.expect("runs start with a head") + high;
+            assert!(writer.append(self, quotient, remainder), "doubled table has room");
+        }
+
+        for fingerprint in wrapped {
+            self.insert(fingerprint).expect("doubled table has room");
+        }
+    }
+
+    /// Reads and clears the cluster wrapping from the last slot around to
+    /// the first, if there is one.
+    fn take_wrapped_cluster(&mut self) -> Vec<u64> {
+        let mut fingerprints = Vec::new();
+        if !self.shifted[0] {
+            return fingerprints;
+        }
+        let start = self.cluster_start(0);
+        let (mut slot, mut quotient) = (start, start);
+        while !self.is_empty_slot(slot) {
+            if slot != start && !self.continuation[slot] {
+                loop {
+                    quotient = self.next(quotient);
+                    if self.occupied[quotient] {
+                        break;
+                    }
+                }
+            }
+            fingerprints.push((quotient as u64) << self.remainder_bits | self.remainder(slot));
+            slot = self.next(slot);
+        }
+        let mut clear = start;
+        while clear != slot {
+            self.occupied.set(clear, false);
+            self.set_flags(clear, false, false);
+            clear = self.next(clear);
+        }
+        fingerprints
+    }
+}
+
+/// Lays out fingerprints given in increasing order front to back.
+#[derive(Default)]
+struct Writer {
+    next_slot: usize,
+    previous: Option<usize>,
+}
+
+impl Writer {
+    /// Places a fingerprint, or returns false if it would run past the
+    /// last slot.
+    fn append(&mut self, table: &mut Table, quotient: usize, remainder: u64) -> bool {
+        let slot = self.next_slot.max(quotient);
+        if slot >= table.slots() {
+            return false;
+        }
+        table.occupied.set(quotient, true);
+        table.set_flags(slot, self.previous == Some(quotient), slot != quotient);
+        table.set_remainder(slot, remainder);
+        table.len += 1;
+        self.next_slot = slot + 1;
+        self.previous = Some(quotient);
+        true
+    }
+}
+
+/// The fingerprints of a Table in increasing order, walking the runs in
+/// quotient order. Slots are tracked without wrapping, so the runs of the
+/// last quotients may continue past the end of the table.
+struct Fingerprints<'a> {
+    table: &'a Table,
+    quotient: usize,
+    slot: usize,
+    remaining: usize,
+}
+
+impl Iterator for Fingerprints<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let table = self.table;
+        let mask = table.slots() - 1;
+        let fingerprint =
+            (self.quotient as u64) << table.remainder_bits | table.remainder(self.slot & mask);
+        self.remaining -= 1;
+        self.slot += 1;
+        if self.remaining > 0 && !table.continuation[self.slot & mask] {
+            // The next run belongs to the next occupied quotient and starts
+            // no earlier than it.
+            self.quotient += 1;
+            while !table.occupied[self.quotient] {
+                self.quotient += 1;
+            }
+            self.slot = self.slot.max(self.quotient);
+        }
+        Some(fingerprint)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuotientFilter, QuotientFilterArgs, QuotientFilterError, Table};
+    use crate::{rng::SplitMix64, BloomFilterContainsResponse, Hashed, PortableHasher, Xxh3};
+
+    fn small(quotient_bits: u32, remainder_bits: u32) -> QuotientFilterArgs {
+        QuotientFilterArgs::builder()
+            .quotient_bits(quotient_bits)
+            .remainder_bits(remainder_bits)
+            .build()
+    }
+
+    #[test]
+    fn quotient_filter_inserts_and_removes() {
+        let mut quotient: QuotientFilter<String> = QuotientFilter::new();
+        quotient.insert("a").unwrap();
+        quotient.insert("b").unwrap();
+        assert_eq!(quotient.len(), 2);
+        assert_eq!(quotient.contains("a"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(quotient.contains("c"), BloomFilterContainsResponse::No);
+
+        assert_eq!(quotient.remove("a"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(quotient.contains("a"), BloomFilterContainsResponse::No);
+        assert_eq!(quotient.contains("b"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(quotient.remove("a"), BloomFilterContainsResponse::No);
+        assert_eq!(quotient.len(), 1);
+    }
+
+    #[test]
+    fn quotient_filter_survives_crowded_wrapping_tables() {
+        // 16 slots nearly full, so clusters run over the end of the table.
+        let mut quotient: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(4, 12));
+        for round in 0..20u32 {
+            let values: Vec<u32> = (round * 15..round * 15 + 15).collect();
+            for value in &values {
+                quotient.insert(value).unwrap();
+            }
+            assert_eq!(quotient.insert(&u32::MAX), Err(QuotientFilterError::Full));
+            assert!(values
+                .iter()
+                .all(|v| quotient.contains(v) == BloomFilterContainsResponse::Maybe));
+            for (i, value) in values.iter().enumerate() {
+                assert_eq!(quotient.remove(value), BloomFilterContainsResponse::Maybe);
+                assert!(values[i + 1..]
+                    .iter()
+                    .all(|v| quotient.contains(v) == BloomFilterContainsResponse::Maybe));
+            }
+            assert!(quotient.is_empty());
+        }
+    }
+
+    #[test]
+    fn quotient_filter_keeps_duplicates() {
+        let mut quotient: QuotientFilter<String> = QuotientFilter::new();
+        quotient.insert("twice").unwrap();
+        quotient.insert("twice").unwrap();
+        quotient.remove("twice");
+        assert_eq!(quotient.contains("twice"), BloomFilterContainsResponse::Maybe);
+        quotient.remove("twice");
+        assert_eq!(quotient.contains("twice"), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn quotient_filter_doubles_without_values() {
+        let mut quotient: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(8, 13));
+        for i in 0..200 {
+            quotient.insert(&i).unwrap();
+        }
+        quotient.double().unwrap();
+        quotient.double().unwrap();
+        assert_eq!((quotient.quotient_bits(), quotient.remainder_bits()), (10, 11));
+        assert_eq!(quotient.len(), 200);
+        assert!((0..200).all(|i| quotient.contains(&i) == BloomFilterContainsResponse::Maybe));
+        for i in 200..700 {
+            quotient.insert(&i).unwrap();
+        }
+        assert!((0..700).all(|i| quotient.contains(&i) == BloomFilterContainsResponse::Maybe));
+
+        let false_positives = (10_000..20_000)
+            .filter(|i| quotient.contains(i) == BloomFilterContainsResponse::Maybe)
+            .count();
+        assert!(false_positives < 30, "{} false positives", false_positives);
+
+        let mut tiny: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(4, 1));
+        assert_eq!(tiny.double(), Err(QuotientFilterError::RemainderExhausted));
+    }
+
+    #[test]
+    fn quotient_filter_merges() {
+        let mut left: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(8, 12));
+        let mut right: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(10, 10));
+        for i in 0..150 {
+            left.insert(&i).unwrap();
+        }
+        for i in 150..600 {
+            right.insert(&i).unwrap();
+        }
+        let merged = left.merge(&right).unwrap();
+        assert_eq!(merged.len(), 600);
+        assert!(merged.load_factor() <= 0.75);
+        assert!((0..600).all(|i| merged.contains(&i) == BloomFilterContainsResponse::Maybe));
+
+        let other: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(8, 8));
+        assert_eq!(
+            left.merge(&other).err(),
+            Some(QuotientFilterError::FingerprintBits { left: 20, right: 16 })
+        );
+        let reseeded: QuotientFilter<u32, Hashed, Xxh3> =
+            QuotientFilter::with_hasher(small(8, 12), Xxh3::with_seed(7));
+        assert_eq!(left.merge(&reseeded).err(), Some(QuotientFilterError::Hasher));
+    }
+
+    #[test]
+    fn quotient_filter_table_matches_sorted_model() {
+        // Fingerprints crowd into a few quotients, so inserts and removes
+        // shift long clusters, including ones wrapping around the table.
+        let mut rng = SplitMix64::new(23);
+        let mut table = Table::new(6, 6);
+        let mut model: Vec<u64> = Vec::new();
+        for _ in 0..20_000 {
+            let quotient = (60 + rng.below(12) as u64) % 64;
+            let fingerprint = quotient << 6 | rng.below(8) as u64;
+            if rng.below(2) == 0 && model.len() < 50 {
+                table.insert(fingerprint).unwrap();
+                let at = model.partition_point(|&f| f <= fingerprint);
+                model.insert(at, fingerprint);
+            } else {
+                let removed = model.iter().position(|&f| f == fingerprint);
+                assert_eq!(table.remove(fingerprint), removed.is_some());
+                if let Some(at) = removed {
+                    model.remove(at);
+                }
+            }
+            assert_eq!(table.fingerprints().collect::<Vec<_>>(), model);
+            assert!(model.iter().all(|&f| table.contains(f)));
+        }
+    }
+
+    #[test]
+    fn quotient_filter_merges_overlapping_clusters() {
+        let mut left: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(5, 10));
+        let mut right: QuotientFilter<u32, Hashed> = QuotientFilter::with_hashed(small(5, 10));
+        for i in 0..22 {
+            left.insert(&i).unwrap();
+            right.insert(&(i + 11)).unwrap();
+        }
+        let merged = left.merge(&right).unwrap();
+        assert_eq!(merged.quotient_bits(), 6);
+        assert_eq!(merged.len(), 44);
+
+        let mut expected: Vec<u64> =
+            left.table.fingerprints().chain(right.table.fingerprints()).collect();
+        expected.sort_unstable();
+        assert_eq!(merged.table.fingerprints().collect::<Vec<_>>(), expected);
+        assert!((0..33).all(|i| merged.contains(&i) == BloomFilterContainsResponse::Maybe));
+
+        let mut merged = merged;
+        for i in 0..33 {
+            assert_eq!(merged.remove(&i), BloomFilterContainsResponse::Maybe);
+        }
+        assert!((11..22).all(|i| merged.contains(&i) == BloomFilterContainsResponse::Maybe));
+    }
+
+    #[test]
+    fn quotient_filter_doubles_wrapped_clusters() {
+        let mut rng = SplitMix64::new(7);
+        for _ in 0..200 {
+            let mut table = Table::new(4, 10);
+            let mut fingerprints: Vec<u64> = (0..14)
+                .map(|_| ((12 + rng.below(8) as u64) % 16) << 10 | rng.below(1024) as u64)
+                .collect();
+            for &fingerprint in &fingerprints {
+                table.insert(fingerprint).unwrap();
+            }
+            fingerprints.sort_unstable();
+            table.double();
+            assert_eq!((table.quotient_bits, table.remainder_bits, table.len), (5, 9, 14));
+            assert_eq!(table.fingerprints().collect::<Vec<_>>(), fingerprints);
+            assert!(fingerprints.iter().all(|&f| table.contains(f)));
+        }
+    }
+
+    #[test]
+    fn quotient_filter_args_from_targets() {
+        let args =
+            QuotientFilterArgs::builder().expected_items(1000).false_positive_rate(0.001).build();
+        assert_eq!((args.quotient_bits(), args.remainder_bits()), (11, 10));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected_items requires a false_positive_rate")]
+    fn quotient_filter_args_reject_expected_items_alone() {
+        QuotientFilterArgs::builder().expected_items(1_000_000).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "false_positive_rate requires expected_items")]
+    fn quotient_filter_args_reject_false_positive_rate_alone() {
+        QuotientFilterArgs::builder().false_positive_rate(0.01).build();
+    }
+}
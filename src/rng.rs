@@ -0,0 +1,23 @@
+/// A small, fast, deterministic pseudo-random generator (SplitMix64) for
+/// the randomized choices some filters make. Not suitable for anything
+/// security related.
+#[derive(Clone, Debug)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        crate::hash::mix64(self.state)
+    }
+
+    /// A value in `0..bound`, by multiply-shift.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+}
@@ -0,0 +1,320 @@
+//! A split-block Bloom filter compatible with Parquet.
+//!
+//! The layout and hashing follow the Parquet specification
+//! (`BloomFilter.md` in apache/parquet-format): values are hashed with
+//! XXH64 (seed 0) over their plain encoding, the upper 32 bits of the hash
+//! select a 256-bit block, and the lower 32 bits, multiplied by eight salts,
+//! select one bit in each of the block's eight 32-bit words. The bitset is
+//! stored as little-endian 32-bit words, which is exactly the payload that
+//! follows the Thrift `BloomFilterHeader` in a Parquet file; writing and
+//! parsing that header is left to the Parquet writer or reader.
+
+use crate::{BloomFilterContainsResponse, DecodeError};
+use std::{
+    borrow::Borrow,
+    io::{self, Write},
+    marker::PhantomData,
+};
+use xxhash_rust::xxh64::xxh64;
+
+const SALT: [u32; 8] = [
+    0x47b6_137b,
+    0x4497_4d91,
+    0x8824_ad5b,
+    0xa2b7_289d,
+    0x7054_95c7,
+    0x2df1_424b,
+    0x9efc_4947,
+    0x5c6b_fb31,
+];
+
+const BLOCK_BYTES: usize = 32;
+
+/// The smallest bitset Parquet writers produce.
+pub const MIN_BYTES: usize = 32;
+
+/// The largest bitset Parquet writers produce.
+pub const MAX_BYTES: usize = 128 * 1024 * 1024;
+
+/// Eight 32-bit words.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(align(32))]
+struct Block([u32; 8]);
+
+/// A split-block Bloom filter (SBBF) that is bit-for-bit compatible with
+/// Parquet column Bloom filters. Uses AVX2 where the CPU supports it and a
+/// scalar implementation elsewhere.
+///
+/// Parquet hashes the plain encoding of a value: the UTF-8 bytes of a
+/// string, or the little-endian bytes of an integer. `insert` and
+/// `contains` take those bytes; `insert_hash` and `contains_hash` take a
+/// precomputed XXH64 hash.
+pub struct SbbfBloomFilter<T> {
+    blocks: Vec<Block>,
+    #[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+    avx2: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: AsRef<[u8]>> SbbfBloomFilter<T> {
+    /// Creates an empty filter of the given size in bytes, rounded up to a
+    /// power of two and clamped between `MIN_BYTES` and `MAX_BYTES`, as
+    /// Parquet writers do.
+    pub fn with_num_bytes(num_bytes: usize) -> Self {
+        let num_bytes = num_bytes.clamp(MIN_BYTES, MAX_BYTES).next_power_of_two();
+        Self::from_blocks(vec![Block::default(); num_bytes / BLOCK_BYTES])
+    }
+
+    /// Creates an empty filter sized for the given number of distinct
+    /// values at the given false-positive probability, using the same
+    /// formula as Parquet writers: m = -8 ndv / ln(1 - fpp^(1/8)).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` is not strictly between 0 and 1.
+    pub fn with_expected_items(expected_items: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false_positive_rate must be between 0 and 1"
+        );
+        let bits = -8.0 * expected_items as f64 / (1.0 - false_positive_rate.powf(1.0 / 8.0)).ln();
+        Self::with_num_bytes(bits as usize / 8)
+    }
+
+    /// Reads a filter from a Parquet bitset (the bytes following the
+    /// `BloomFilterHeader`). The length must be a non-zero multiple of 32.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(BLOCK_BYTES) {
+            return Err(DecodeError::InvalidLength(bytes.len()));
+        }
+        let blocks = bytes
+            .chunks_exact(BLOCK_BYTES)
+            .map(|chunk| {
+                let mut block = Block::default();
+                for (word, bytes) in block.0.iter_mut().zip(chunk.chunks_exact(4)) {
+                    *word = u32::from_le_bytes(bytes.try_into().unwrap());
+                }
+                block
+            })
+            .collect();
+        Ok(Self::from_blocks(blocks))
+    }
+
+    fn from_blocks(blocks: Vec<Block>) -> Self {
+        Self { blocks, avx2: avx2_available(), _phantom: PhantomData }
+    }
+
+    /// The size of the bitset in bytes.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_BYTES
+    }
+
+    /// Serializes the bitset in the Parquet layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.0.iter().flat_map(|word| word.to_le_bytes()))
+            .collect()
+    }
+
+    /// Writes the bitset in the Parquet layout to the given writer.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for block in &self.blocks {
+            for word in block.0 {
+                writer.write_all(&word.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts the plain encoding of a value into the filter.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        Q: AsRef<[u8]> + ?Sized,
+    {
+        self.insert_hash(hash(value.as_ref()));
+    }
+
+    /// Checks if the filter contains the plain encoding of a value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        Q: AsRef<[u8]> + ?Sized,
+    {
+        self.contains_hash(hash(value.as_ref()))
+    }
+
+    /// Inserts a value by its XXH64 hash.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let idx = self.block_index(hash);
+        let block = &mut self.blocks[idx];
+        #[cfg(target_arch = "x86_64")]
+        if self.avx2 {
+            // SAFETY: AVX2 support was detected at construction.
+            unsafe { avx2::insert(block, hash as u32) };
+            return;
+        }
+        scalar::insert(block, hash as u32);
+    }
+
+    /// Checks if the filter contains a value by its XXH64 hash.
+    pub fn contains_hash(&self, hash: u64) -> BloomFilterContainsResponse {
+        let block = &self.blocks[self.block_index(hash)];
+        #[cfg(target_arch = "x86_64")]
+        let found = if self.avx2 {
+            // SAFETY: AVX2 support was detected at construction.
+            unsafe { avx2::check(block, hash as u32) }
+        } else {
+            scalar::check(block, hash as u32)
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let found = scalar::check(block, hash as u32);
+
+        if found {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+
+    /// The upper 32 bits of the hash pick the block by multiply-shift.
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+}
+
+/// Hashes bytes the way Parquet does: XXH64 with seed 0.
+pub fn hash(bytes: &[u8]) -> u64 {
+    xxh64(bytes, 0)
+}
+
+fn avx2_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+mod scalar {
+    use super::{Block, SALT};
+
+    /// One bit in each word, chosen by the top five bits of key * salt.
+    fn mask(key: u32) -> [u32; 8] {
+        SALT.map(|salt| 1 << (key.wrapping_mul(salt) >> 27))
+    }
+
+    pub(super) fn insert(block: &mut Block, key: u32) {
+        for (word, bit) in block.0.iter_mut().zip(mask(key)) {
+            *word |= bit;
+        }
+    }
+
+    pub(super) fn check(block: &Block, key: u32) -> bool {
+        block.0.iter().zip(mask(key)).all(|(word, bit)| word & bit != 0)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{Block, SALT};
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn mask(key: u32) -> __m256i {
+        let salt = _mm256_loadu_si256(SALT.as_ptr() as *const __m256i);
+        let products = _mm256_mullo_epi32(_mm256_set1_epi32(key as i32), salt);
+        _mm256_sllv_epi32(_mm256_set1_epi32(1), _mm256_srli_epi32::<27>(products))
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn insert(block: &mut Block, key: u32) {
+        let ptr = block.0.as_mut_ptr() as *mut __m256i;
+        _mm256_store_si256(ptr, _mm256_or_si256(_mm256_load_si256(ptr), mask(key)));
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn check(block: &Block, key: u32) -> bool {
+        let words = _mm256_load_si256(block.0.as_ptr() as *const __m256i);
+        // testc is set when every bit of the mask is also set in the words.
+        _mm256_testc_si256(words, mask(key)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash, scalar, Block, SbbfBloomFilter};
+    use crate::{BloomFilterContainsResponse, DecodeError};
+
+    #[test]
+    fn sbbf_hash_matches_parquet() {
+        assert_eq!(hash(b""), 17_241_709_254_077_376_921);
+    }
+
+    #[test]
+    fn sbbf_reads_filter_written_by_parquet_mr() {
+        // Written by parquet-mr for the strings "a0" to "a9".
+        let bitset: &[u8] = &[
+            200, 1, 80, 20, 64, 68, 8, 109, 6, 37, 4, 67, 144, 80, 96, 32, 8, 132, 43, 33, 0, 5,
+            99, 65, 2, 0, 224, 44, 64, 78, 96, 4,
+        ];
+        let sbbf: SbbfBloomFilter<String> = SbbfBloomFilter::from_bytes(bitset).unwrap();
+        for i in 0..10 {
+            assert_eq!(sbbf.contains(&format!("a{}", i)), BloomFilterContainsResponse::Maybe);
+        }
+
+        // Building the same filter reproduces it bit for bit.
+        let mut rebuilt: SbbfBloomFilter<String> = SbbfBloomFilter::with_num_bytes(32);
+        (0..10).for_each(|i| rebuilt.insert(&format!("a{}", i)));
+        assert_eq!(rebuilt.to_bytes(), bitset);
+        let mut written = Vec::new();
+        rebuilt.write_to(&mut written).unwrap();
+        assert_eq!(written, bitset);
+    }
+
+    #[test]
+    fn sbbf_simd_matches_scalar() {
+        let mut simd: SbbfBloomFilter<Vec<u8>> = SbbfBloomFilter::with_num_bytes(1024);
+        let mut fallback: SbbfBloomFilter<Vec<u8>> = SbbfBloomFilter::with_num_bytes(1024);
+        fallback.avx2 = false;
+        for i in 0..1_000u64 {
+            simd.insert(&i.to_le_bytes()[..]);
+            fallback.insert(&i.to_le_bytes()[..]);
+        }
+        assert_eq!(simd.to_bytes(), fallback.to_bytes());
+        for i in 0..2_000u64 {
+            assert_eq!(simd.contains(&i.to_le_bytes()[..]), fallback.contains(&i.to_le_bytes()[..]));
+        }
+
+        for key in [0, 1, 0xdead_beef, u32::MAX] {
+            let mut block = Block::default();
+            scalar::insert(&mut block, key);
+            assert!(block.0.iter().all(|word| word.is_power_of_two()));
+            assert!(scalar::check(&block, key));
+        }
+    }
+
+    #[test]
+    fn sbbf_sizes_like_parquet_writers() {
+        let sizes = [(0, 32), (33, 64), (1024, 1024), (usize::MAX, 128 * 1024 * 1024)];
+        for (requested, actual) in sizes {
+            assert_eq!(SbbfBloomFilter::<String>::with_num_bytes(requested).num_bytes(), actual);
+        }
+        // 1000 values at 1% need 9681 bits, rounded up to 2048 bytes.
+        assert_eq!(SbbfBloomFilter::<String>::with_expected_items(1_000, 0.01).num_bytes(), 2048);
+        assert!(matches!(
+            SbbfBloomFilter::<String>::from_bytes(&[0; 48]),
+            Err(DecodeError::InvalidLength(48))
+        ));
+    }
+}
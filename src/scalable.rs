@@ -0,0 +1,326 @@
+use crate::{
+    BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher, Bytes, Hashed,
+    KeyEncoding, Xxh3,
+};
+use std::{borrow::Borrow, hash::Hash};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScalableBloomFilterArgs {
+    initial_capacity: usize,
+    false_positive_rate: f64,
+    growth_factor: usize,
+    tightening_ratio: f64,
+}
+
+impl Default for ScalableBloomFilterArgs {
+    fn default() -> Self {
+        Self {
+            initial_capacity: 1024,
+            false_positive_rate: 0.01,
+            growth_factor: 2,
+            tightening_ratio: 0.85,
+        }
+    }
+}
+
+impl ScalableBloomFilterArgs {
+    /// Creates a builder for ScalableBloomFilterArgs.
+    pub fn builder() -> ScalableBloomFilterArgsBuilder {
+        ScalableBloomFilterArgsBuilder::default()
+    }
+
+    /// The number of items the first slice holds.
+    pub fn initial_capacity(&self) -> usize {
+        self.initial_capacity
+    }
+
+    /// The bound on the overall false-positive probability.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.false_positive_rate
+    }
+
+    /// How many times larger each slice is than the one before.
+    pub fn growth_factor(&self) -> usize {
+        self.growth_factor
+    }
+
+    /// How much tighter the false-positive probability of each slice is
+    /// than that of the one before.
+    pub fn tightening_ratio(&self) -> f64 {
+        self.tightening_ratio
+    }
+
+    /// The arguments of the slice at the given position. Slice i holds
+    /// n0 * s^i items at false-positive probability P (1 - r) r^i, so the
+    /// probabilities of all slices sum to less than P.
+    fn slice(&self, position: usize) -> (usize, BloomFilterArgs) {
+        let capacity = self
+            .initial_capacity
+            .saturating_mul(self.growth_factor.saturating_pow(position as u32));
+        let args = BloomFilterArgs::builder()
+            .expected_items(capacity)
+            .false_positive_rate(self.slice_false_positive_rate(position).max(f64::MIN_POSITIVE))
+            .build();
+        (capacity, args)
+    }
+
+    fn slice_false_positive_rate(&self, position: usize) -> f64 {
+        self.false_positive_rate
+            * (1.0 - self.tightening_ratio)
+            * self.tightening_ratio.powi(position as i32)
+    }
+}
+
+/// Builds ScalableBloomFilterArgs. Anything left unset falls back to
+/// `ScalableBloomFilterArgs::default()`.
+#[derive(Clone, Debug, Default)]
+pub struct ScalableBloomFilterArgsBuilder {
+    initial_capacity: Option<usize>,
+    false_positive_rate: Option<f64>,
+    growth_factor: Option<usize>,
+    tightening_ratio: Option<f64>,
+}
+
+impl ScalableBloomFilterArgsBuilder {
+    /// Sets the number of items the first slice holds.
+    pub fn initial_capacity(mut self, initial_capacity: usize) -> Self {
+        self.initial_capacity = Some(initial_capacity);
+        self
+    }
+
+    /// Sets the bound on the overall false-positive probability (P).
+    pub fn false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    /// Sets how many times larger each slice is than the one before (s),
+    /// at least two so that the number of slices grows logarithmically.
+    pub fn growth_factor(mut self, growth_factor: usize) -> Self {
+        self.growth_factor = Some(growth_factor);
+        self
+    }
+
+    /// Sets how much tighter the false-positive probability of each slice
+    /// is than that of the one before (r).
+    pub fn tightening_ratio(mut self, tightening_ratio: f64) -> Self {
+        self.tightening_ratio = Some(tightening_ratio);
+        self
+    }
+
+    /// Builds the arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial_capacity` is zero, `growth_factor` is less than
+    /// two, or `false_positive_rate` or `tightening_ratio` is not strictly
+    /// between 0 and 1.
+    pub fn build(self) -> ScalableBloomFilterArgs {
+        let default = ScalableBloomFilterArgs::default();
+        let args = ScalableBloomFilterArgs {
+            initial_capacity: self.initial_capacity.unwrap_or(default.initial_capacity),
+            false_positive_rate: self.false_positive_rate.unwrap_or(default.false_positive_rate),
+            growth_factor: self.growth_factor.unwrap_or(default.growth_factor),
+            tightening_ratio: self.tightening_ratio.unwrap_or(default.tightening_ratio),
+        };
+        assert!(args.initial_capacity > 0, "initial_capacity must be greater than zero");
+        assert!(
+            args.false_positive_rate > 0.0 && args.false_positive_rate < 1.0,
+            "false_positive_rate must be between 0 and 1"
+        );
+        assert!(args.growth_factor >= 2, "growth_factor must be at least two");
+        assert!(
+            args.tightening_ratio > 0.0 && args.tightening_ratio < 1.0,
+            "tightening_ratio must be between 0 and 1"
+        );
+        args
+    }
+}
+
+/// A bloom filter that grows as items are added (Almeida et al., "Scalable
+/// Bloom Filters"). Once the newest BloomFilter slice holds its capacity,
+/// a larger slice with a tighter false-positive probability is added, which
+/// keeps the overall false-positive probability below the configured bound
+/// no matter how many items are inserted.
+pub struct ScalableBloomFilter<T, E = Bytes, S = Xxh3> {
+    slices: Vec<BloomFilter<T, E, S>>,
+    capacity: usize,
+    count: usize,
+    len: usize,
+    args: ScalableBloomFilterArgs,
+    hasher: S,
+}
+
+impl<T: AsRef<[u8]>> ScalableBloomFilter<T> {
+    /// Creates a new ScalableBloomFilter with the default arguments.
+    pub fn new() -> Self {
+        ScalableBloomFilter::with(ScalableBloomFilterArgs::default())
+    }
+
+    /// Creates a new ScalableBloomFilter with the given arguments.
+    pub fn with(args: ScalableBloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for ScalableBloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> ScalableBloomFilter<T, Hashed> {
+    /// Creates a new ScalableBloomFilter over `Hash` values with the
+    /// default arguments.
+    pub fn new_hashed() -> Self {
+        ScalableBloomFilter::with_hashed(ScalableBloomFilterArgs::default())
+    }
+
+    /// Creates a new ScalableBloomFilter over `Hash` values with the given
+    /// arguments.
+    pub fn with_hashed(args: ScalableBloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher + Clone> ScalableBloomFilter<T, E, S> {
+    /// Creates a new ScalableBloomFilter with the given arguments and hash
+    /// strategy, shared by all slices.
+    pub fn with_hasher(args: ScalableBloomFilterArgs, hasher: S) -> Self {
+        let (capacity, slice_args) = args.slice(0);
+        Self {
+            slices: vec![BloomFilter::with_hasher(slice_args, hasher.clone())],
+            capacity,
+            count: 0,
+            len: 0,
+            args,
+            hasher,
+        }
+    }
+
+    /// The arguments of the ScalableBloomFilter.
+    pub fn args(&self) -> &ScalableBloomFilterArgs {
+        &self.args
+    }
+
+    /// The BloomFilter slices, oldest first.
+    pub fn slices(&self) -> &[BloomFilter<T, E, S>] {
+        &self.slices
+    }
+
+    /// The number of distinct values inserted. Values that were false
+    /// positives when inserted are not counted.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The upper bound on the current false-positive probability, given
+    /// the slices allocated so far. Always below the configured rate.
+    pub fn false_positive_rate(&self) -> f64 {
+        let miss: f64 = (0..self.slices.len())
+            .map(|position| 1.0 - self.args.slice_false_positive_rate(position))
+            .product();
+        1.0 - miss
+    }
+
+    /// Inserts a new value into the ScalableBloomFilter, adding a slice
+    /// first if the newest one is full.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        // Re-inserting a value must not use up capacity.
+        if self.contains(value) == BloomFilterContainsResponse::Maybe {
+            return;
+        }
+        if self.count >= self.capacity {
+            let (capacity, slice_args) = self.args.slice(self.slices.len());
+            self.slices.push(BloomFilter::with_hasher(slice_args, self.hasher.clone()));
+            self.capacity = capacity;
+            self.count = 0;
+        }
+        self.slices.last_mut().unwrap().insert(value);
+        self.count += 1;
+        self.len += 1;
+    }
+
+    /// Checks if the ScalableBloomFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        // The newest slices hold the most values, so check them first.
+        for slice in self.slices.iter().rev() {
+            if slice.contains(value) == BloomFilterContainsResponse::Maybe {
+                return BloomFilterContainsResponse::Maybe;
+            }
+        }
+        BloomFilterContainsResponse::No
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScalableBloomFilter, ScalableBloomFilterArgs};
+    use crate::{BloomFilterContainsResponse, Hashed};
+
+    #[test]
+    fn scalable_bloom_filter_grows_without_false_negatives() {
+        let args = ScalableBloomFilterArgs::builder()
+            .initial_capacity(100)
+            .false_positive_rate(0.01)
+            .build();
+        let mut scalable: ScalableBloomFilter<u32, Hashed> = ScalableBloomFilter::with_hashed(args);
+        (0..5_000).for_each(|i| scalable.insert(&i));
+
+        assert!(scalable.slices().len() >= 5);
+        assert!(scalable.slices().windows(2).all(|pair| pair[1].bits() > pair[0].bits()));
+        assert!((0..5_000).all(|i| scalable.contains(&i) == BloomFilterContainsResponse::Maybe));
+        assert!(scalable.false_positive_rate() < 0.01);
+    }
+
+    #[test]
+    fn scalable_bloom_filter_keeps_false_positive_rate_bounded() {
+        let args = ScalableBloomFilterArgs::builder()
+            .initial_capacity(64)
+            .false_positive_rate(0.01)
+            .build();
+        let mut scalable: ScalableBloomFilter<u32, Hashed> = ScalableBloomFilter::with_hashed(args);
+        (0..20_000).for_each(|i| scalable.insert(&i));
+
+        let false_positives = (20_000..120_000)
+            .filter(|i| scalable.contains(i) == BloomFilterContainsResponse::Maybe)
+            .count();
+        assert!(false_positives < 1_000, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn scalable_bloom_filter_does_not_count_duplicates() {
+        let mut scalable: ScalableBloomFilter<String> = ScalableBloomFilter::new();
+        assert!(scalable.is_empty());
+        (0..10).for_each(|_| scalable.insert("same"));
+        assert_eq!(scalable.len(), 1);
+        assert_eq!(scalable.slices().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "tightening_ratio")]
+    fn scalable_bloom_filter_args_reject_invalid_tightening_ratio() {
+        ScalableBloomFilterArgs::builder().tightening_ratio(1.0).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "growth_factor")]
+    fn scalable_bloom_filter_args_reject_growth_factor_one() {
+        ScalableBloomFilterArgs::builder().growth_factor(1).build();
+    }
+}
@@ -0,0 +1,164 @@
+//! Serde support, enabled by the `serde` feature.
+
+use crate::{
+    format::{DecodeError, Header},
+    BloomFilter, BloomFilterArgs, PortableHasher,
+};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "BloomFilterArgs")]
+struct ArgsRepr {
+    bits: usize,
+    hashes: usize,
+}
+
+impl Serialize for BloomFilterArgs {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        ArgsRepr { bits: self.bits, hashes: self.hashes }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BloomFilterArgs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ArgsRepr { bits, hashes } = ArgsRepr::deserialize(deserializer)?;
+        if bits == 0 {
+            return Err(de::Error::invalid_value(de::Unexpected::Unsigned(0), &"a positive bit count"));
+        }
+        if hashes == 0 {
+            return Err(de::Error::invalid_value(de::Unexpected::Unsigned(0), &"a positive hash count"));
+        }
+        Ok(BloomFilterArgs { bits, hashes })
+    }
+}
+
+/// The serialized form of a BloomFilter. The bits are packed the same way
+/// as in the binary format.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "BloomFilter")]
+struct FilterRepr {
+    bits: u64,
+    hashes: u32,
+    scheme: u8,
+    seed: u64,
+    data: Payload,
+}
+
+/// Packed bits, serialized as a byte string rather than a sequence.
+struct Payload(Vec<u8>);
+
+impl Serialize for Payload {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PayloadVisitor;
+
+        impl<'de> Visitor<'de> for PayloadVisitor {
+            type Value = Payload;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a byte string")
+            }
+
+            fn visit_bytes<Er: de::Error>(self, bytes: &[u8]) -> Result<Payload, Er> {
+                Ok(Payload(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<Er: de::Error>(self, bytes: Vec<u8>) -> Result<Payload, Er> {
+                Ok(Payload(bytes))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Payload, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Payload(bytes))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(PayloadVisitor)
+    }
+}
+
+impl<T, E, S: PortableHasher> Serialize for BloomFilter<T, E, S> {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        FilterRepr {
+            bits: self.bits.len() as u64,
+            hashes: self.hashes as u32,
+            scheme: S::SCHEME,
+            seed: self.hasher.seed(),
+            data: Payload(self.bits.to_bytes()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T, E, S: PortableHasher> Deserialize<'de> for BloomFilter<T, E, S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = FilterRepr::deserialize(deserializer)?;
+        let header = Header::new(repr.scheme, repr.bits, repr.hashes, repr.seed)
+            .and_then(|header| header.check_scheme::<S>().map(|()| header))
+            .map_err(de::Error::custom)?;
+        if repr.data.0.len() != header.payload_len() {
+            return Err(de::Error::custom(DecodeError::InvalidHeader(
+                "data length does not match bit count",
+            )));
+        }
+        Ok(Self::from_parts(&header, &repr.data.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, Bytes, PortableHasher, Xxh3};
+
+    #[test]
+    fn bloom_filter_round_trips_through_serde() {
+        let args = BloomFilterArgs::builder().bits(20).hashes(2).build();
+        let mut bloom_filter: BloomFilter<String, Bytes, Xxh3> =
+            BloomFilter::with_hasher(args, Xxh3::with_seed(5));
+        bloom_filter.insert("serde");
+
+        let json = serde_json::to_string(&bloom_filter).unwrap();
+        assert!(json.starts_with(r#"{"bits":20,"hashes":2,"scheme":1,"seed":5,"data":["#));
+        let decoded: BloomFilter<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_bytes(), bloom_filter.to_bytes());
+        assert_eq!(decoded.contains("serde"), BloomFilterContainsResponse::Maybe);
+    }
+
+    #[test]
+    fn bloom_filter_deserialize_validates_parameters() {
+        let decode = |json: &str| serde_json::from_str::<BloomFilter<String>>(json).err().unwrap();
+        let error = decode(r#"{"bits":20,"hashes":2,"scheme":1,"seed":5,"data":[0,0]}"#);
+        assert!(error.to_string().contains("data length"));
+        let error = decode(r#"{"bits":20,"hashes":0,"scheme":1,"seed":5,"data":[0,0,0]}"#);
+        assert!(error.to_string().contains("hash count is zero"));
+        let error = decode(r#"{"bits":20,"hashes":2,"scheme":9,"seed":5,"data":[0,0,0]}"#);
+        assert!(error.to_string().contains("hash scheme 9"));
+    }
+
+    #[test]
+    fn bloom_filter_args_and_response_round_trip_through_serde() {
+        let args = BloomFilterArgs::builder().bits(64).hashes(3).build();
+        let json = serde_json::to_string(&args).unwrap();
+        assert_eq!(json, r#"{"bits":64,"hashes":3}"#);
+        assert_eq!(serde_json::from_str::<BloomFilterArgs>(&json).unwrap(), args);
+        assert!(serde_json::from_str::<BloomFilterArgs>(r#"{"bits":0,"hashes":3}"#).is_err());
+
+        let json = serde_json::to_string(&BloomFilterContainsResponse::Maybe).unwrap();
+        assert_eq!(json, r#""Maybe""#);
+        assert_eq!(
+            serde_json::from_str::<BloomFilterContainsResponse>(&json).unwrap(),
+            BloomFilterContainsResponse::Maybe
+        );
+    }
+}
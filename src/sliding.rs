@@ -0,0 +1,397 @@
+use crate::{
+    BloomFilter, BloomFilterArgs, BloomFilterContainsResponse, BloomHasher, Bytes, Hashed,
+    KeyEncoding, Xxh3,
+};
+use std::{
+    borrow::Borrow,
+    collections::VecDeque,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// A source of monotonic time for a SlidingWindowBloomFilter.
+pub trait Clock {
+    /// The time elapsed since some fixed origin. Must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+/// The `Clock` backed by `Instant`, measuring from its creation.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    /// Creates a SystemClock starting at zero now.
+    pub fn new() -> Self {
+        Self { origin: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// When a SlidingWindowBloomFilter starts a new generation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    /// Every time the given interval elapses on the clock.
+    Interval(Duration),
+    /// Once the newest generation has taken the given number of inserts.
+    Inserts(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlidingWindowBloomFilterArgs {
+    generation: BloomFilterArgs,
+    generations: usize,
+    rotation: Rotation,
+}
+
+impl Default for SlidingWindowBloomFilterArgs {
+    fn default() -> Self {
+        Self {
+            generation: BloomFilterArgs::default(),
+            generations: 4,
+            rotation: Rotation::Inserts(1024),
+        }
+    }
+}
+
+impl SlidingWindowBloomFilterArgs {
+    /// Creates a builder for SlidingWindowBloomFilterArgs.
+    pub fn builder() -> SlidingWindowBloomFilterArgsBuilder {
+        SlidingWindowBloomFilterArgsBuilder::default()
+    }
+
+    /// The arguments of each generation's BloomFilter.
+    pub fn generation(&self) -> BloomFilterArgs {
+        self.generation
+    }
+
+    /// The number of generations in the ring.
+    pub fn generations(&self) -> usize {
+        self.generations
+    }
+
+    /// When a new generation starts.
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+}
+
+/// Builds SlidingWindowBloomFilterArgs. Anything left unset falls back to
+/// `SlidingWindowBloomFilterArgs::default()`.
+#[derive(Clone, Debug, Default)]
+pub struct SlidingWindowBloomFilterArgsBuilder {
+    generation: Option<BloomFilterArgs>,
+    generations: Option<usize>,
+    rotation: Option<Rotation>,
+}
+
+impl SlidingWindowBloomFilterArgsBuilder {
+    /// Sets the arguments of each generation's BloomFilter. Size them for
+    /// the values one generation takes.
+    pub fn generation(mut self, generation: BloomFilterArgs) -> Self {
+        self.generation = Some(generation);
+        self
+    }
+
+    /// Sets the number of generations in the ring.
+    pub fn generations(mut self, generations: usize) -> Self {
+        self.generations = Some(generations);
+        self
+    }
+
+    /// Starts a new generation every time `interval` elapses.
+    pub fn rotate_every(mut self, interval: Duration) -> Self {
+        self.rotation = Some(Rotation::Interval(interval));
+        self
+    }
+
+    /// Starts a new generation once the newest one has taken `inserts`
+    /// inserts.
+    pub fn rotate_after_inserts(mut self, inserts: usize) -> Self {
+        self.rotation = Some(Rotation::Inserts(inserts));
+        self
+    }
+
+    /// Builds the arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `generations`, the rotation interval or the rotation
+    /// insert count is zero.
+    pub fn build(self) -> SlidingWindowBloomFilterArgs {
+        let default = SlidingWindowBloomFilterArgs::default();
+        let args = SlidingWindowBloomFilterArgs {
+            generation: self.generation.unwrap_or(default.generation),
+            generations: self.generations.unwrap_or(default.generations),
+            rotation: self.rotation.unwrap_or(default.rotation),
+        };
+        assert!(args.generations > 0, "generations must be greater than zero");
+        match args.rotation {
+            Rotation::Interval(interval) => {
+                assert!(!interval.is_zero(), "rotation interval must be greater than zero")
+            }
+            Rotation::Inserts(inserts) => {
+                assert!(inserts > 0, "rotation inserts must be greater than zero")
+            }
+        }
+        args
+    }
+}
+
+/// A bloom filter that forgets: values are inserted into the newest of a
+/// ring of BloomFilter generations, and once a new generation starts the
+/// oldest one is cleared and reused. A value is therefore reported for at
+/// least `generations - 1` and at most `generations` rotation periods
+/// after its last insert.
+pub struct SlidingWindowBloomFilter<T, E = Bytes, S = Xxh3, C = SystemClock> {
+    /// Newest first.
+    generations: VecDeque<BloomFilter<T, E, S>>,
+    args: SlidingWindowBloomFilterArgs,
+    clock: C,
+    /// When the newest generation started, for interval rotation.
+    started: Duration,
+    /// Inserts into the newest generation, for insert count rotation.
+    inserts: usize,
+}
+
+impl<T: AsRef<[u8]>> SlidingWindowBloomFilter<T> {
+    /// Creates a new SlidingWindowBloomFilter with the default arguments.
+    pub fn new() -> Self {
+        SlidingWindowBloomFilter::with(SlidingWindowBloomFilterArgs::default())
+    }
+
+    /// Creates a new SlidingWindowBloomFilter with the given arguments.
+    pub fn with(args: SlidingWindowBloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for SlidingWindowBloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> SlidingWindowBloomFilter<T, Hashed> {
+    /// Creates a new SlidingWindowBloomFilter over `Hash` values with the
+    /// default arguments.
+    pub fn new_hashed() -> Self {
+        SlidingWindowBloomFilter::with_hashed(SlidingWindowBloomFilterArgs::default())
+    }
+
+    /// Creates a new SlidingWindowBloomFilter over `Hash` values with the
+    /// given arguments.
+    pub fn with_hashed(args: SlidingWindowBloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher + Clone> SlidingWindowBloomFilter<T, E, S> {
+    /// Creates a new SlidingWindowBloomFilter with the given arguments and
+    /// hash strategy, shared by all generations.
+    pub fn with_hasher(args: SlidingWindowBloomFilterArgs, hasher: S) -> Self {
+        Self::with_clock(args, hasher, SystemClock::new())
+    }
+}
+
+impl<T, E, S: BloomHasher + Clone, C: Clock> SlidingWindowBloomFilter<T, E, S, C> {
+    /// Creates a new SlidingWindowBloomFilter with the given arguments, hash
+    /// strategy and clock. The first generation starts at the clock's
+    /// current time.
+    pub fn with_clock(args: SlidingWindowBloomFilterArgs, hasher: S, clock: C) -> Self {
+        let generations = (0..args.generations)
+            .map(|_| BloomFilter::with_hasher(args.generation, hasher.clone()))
+            .collect();
+        let started = clock.now();
+        Self { generations, args, clock, started, inserts: 0 }
+    }
+
+    /// The arguments of the SlidingWindowBloomFilter.
+    pub fn args(&self) -> &SlidingWindowBloomFilterArgs {
+        &self.args
+    }
+
+    /// The clock of the SlidingWindowBloomFilter.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// The generations, newest first. Generations whose time has run out
+    /// are only cleared by the next `insert` or `expire`.
+    pub fn generations(&self) -> impl Iterator<Item = &BloomFilter<T, E, S>> {
+        self.generations.iter()
+    }
+
+    /// Inserts a new value into the newest generation, first starting new
+    /// generations as the rotation requires.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        self.expire();
+        if let Rotation::Inserts(inserts) = self.args.rotation {
+            if self.inserts >= inserts {
+                self.rotate();
+            }
+        }
+        self.generations[0].insert(value);
+        self.inserts += 1;
+    }
+
+    /// Checks if any live generation contains the given value. Generations
+    /// that expired by the clock are skipped even before they are cleared.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let live = self.generations.len() - self.elapsed_rotations().min(self.generations.len());
+        self.generations
+            .iter()
+            .take(live)
+            .fold(BloomFilterContainsResponse::No, |response, generation| {
+                response | generation.contains(value)
+            })
+    }
+
+    /// Starts as many new generations as the clock requires, clearing the
+    /// expired ones.
+    pub fn expire(&mut self) {
+        if let Rotation::Interval(interval) = self.args.rotation {
+            for _ in 0..self.elapsed_rotations().min(self.generations.len()) {
+                self.rotate();
+            }
+            // Keep generations aligned to the interval, however long the
+            // pause was.
+            let now = self.clock.now();
+            let elapsed = now.saturating_sub(self.started).as_nanos();
+            self.started = now - Duration::from_nanos((elapsed % interval.as_nanos()) as u64);
+        }
+    }
+
+    /// Starts a new generation now, clearing the oldest one.
+    pub fn rotate(&mut self) {
+        let mut oldest = self.generations.pop_back().expect("at least one generation");
+        oldest.bits.clear();
+        self.generations.push_front(oldest);
+        self.inserts = 0;
+    }
+
+    /// How many interval rotations are due.
+    fn elapsed_rotations(&self) -> usize {
+        match self.args.rotation {
+            Rotation::Interval(interval) => {
+                let elapsed = self.clock.now().saturating_sub(self.started);
+                (elapsed.as_nanos() / interval.as_nanos()) as usize
+            }
+            Rotation::Inserts(_) => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, SlidingWindowBloomFilter, SlidingWindowBloomFilterArgs};
+    use crate::{BloomFilterContainsResponse, Bytes, Xxh3};
+    use std::{cell::Cell, rc::Rc, time::Duration};
+
+    #[derive(Clone, Default)]
+    struct MockClock(Rc<Cell<Duration>>);
+
+    impl MockClock {
+        fn set(&self, secs: u64) {
+            self.0.set(Duration::from_secs(secs));
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    fn windowed(clock: &MockClock) -> SlidingWindowBloomFilter<String, Bytes, Xxh3, MockClock> {
+        let args = SlidingWindowBloomFilterArgs::builder()
+            .generations(3)
+            .rotate_every(Duration::from_secs(10))
+            .build();
+        SlidingWindowBloomFilter::with_clock(args, Xxh3::default(), clock.clone())
+    }
+
+    #[test]
+    fn sliding_window_bloom_filter_forgets_after_window() {
+        let clock = MockClock::default();
+        let mut window = windowed(&clock);
+        window.insert("a");
+        clock.set(15);
+        window.insert("b");
+
+        clock.set(29);
+        assert_eq!(window.contains("a"), BloomFilterContainsResponse::Maybe);
+        clock.set(30);
+        assert_eq!(window.contains("a"), BloomFilterContainsResponse::No);
+        assert_eq!(window.contains("b"), BloomFilterContainsResponse::Maybe);
+        clock.set(40);
+        assert_eq!(window.contains("b"), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn sliding_window_bloom_filter_expires_generations() {
+        let clock = MockClock::default();
+        let mut window = windowed(&clock);
+        window.insert("a");
+        clock.set(25);
+        window.insert("b");
+        assert_eq!(window.generations().filter(|g| g.count_ones() > 0).count(), 2);
+
+        // A long pause clears every generation, and rotation stays aligned
+        // to the interval.
+        clock.set(1_005);
+        window.expire();
+        assert!(window.generations().all(|g| g.count_ones() == 0));
+        window.insert("c");
+        clock.set(1_019);
+        assert_eq!(window.contains("c"), BloomFilterContainsResponse::Maybe);
+        clock.set(1_020);
+        window.expire();
+        assert_eq!(window.contains("c"), BloomFilterContainsResponse::Maybe);
+        clock.set(1_030);
+        assert_eq!(window.contains("c"), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn sliding_window_bloom_filter_rotates_on_inserts() {
+        let args = SlidingWindowBloomFilterArgs::builder()
+            .generations(2)
+            .rotate_after_inserts(100)
+            .build();
+        let mut window: SlidingWindowBloomFilter<String> = SlidingWindowBloomFilter::with(args);
+        let values: Vec<String> = (0..300).map(|i| i.to_string()).collect();
+        for value in &values {
+            window.insert(value.as_str());
+        }
+        assert!(values[100..]
+            .iter()
+            .all(|v| window.contains(v.as_str()) == BloomFilterContainsResponse::Maybe));
+        let remembered = values[..100]
+            .iter()
+            .filter(|v| window.contains(v.as_str()) == BloomFilterContainsResponse::Maybe)
+            .count();
+        assert!(remembered < 10, "{} old values remembered", remembered);
+    }
+}
@@ -0,0 +1,386 @@
+use crate::{
+    hash::HashIndices, rng::SplitMix64, BloomFilterContainsResponse, BloomHasher, Bytes, Hashed,
+    KeyEncoding, Xxh3,
+};
+use std::{borrow::Borrow, hash::Hash, marker::PhantomData};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StableBloomFilterArgs {
+    cells: usize,
+    cell_bits: u32,
+    hashes: usize,
+    decrements: usize,
+}
+
+impl Default for StableBloomFilterArgs {
+    fn default() -> Self {
+        let (cells, cell_bits, hashes) = (8192, 3, 3);
+        let decrements = optimal_decrements(cells, cell_bits, hashes, 0.01);
+        Self { cells, cell_bits, hashes, decrements }
+    }
+}
+
+impl StableBloomFilterArgs {
+    /// Creates a builder for StableBloomFilterArgs.
+    pub fn builder() -> StableBloomFilterArgsBuilder {
+        StableBloomFilterArgsBuilder::default()
+    }
+
+    /// The number of cells (m).
+    pub fn cells(&self) -> usize {
+        self.cells
+    }
+
+    /// The number of bits in each cell (d).
+    pub fn cell_bits(&self) -> u32 {
+        self.cell_bits
+    }
+
+    /// The number of hash functions (K) applied to each value.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// The number of cells decremented on each insert (P).
+    pub fn decrements(&self) -> usize {
+        self.decrements
+    }
+
+    /// The value cells are set to on insert, 2^d - 1.
+    fn max(&self) -> u64 {
+        (1 << self.cell_bits) - 1
+    }
+}
+
+/// Builds StableBloomFilterArgs.
+#[derive(Clone, Debug, Default)]
+pub struct StableBloomFilterArgsBuilder {
+    cells: Option<usize>,
+    cell_bits: Option<u32>,
+    hashes: Option<usize>,
+    decrements: Option<usize>,
+    false_positive_rate: Option<f64>,
+}
+
+impl StableBloomFilterArgsBuilder {
+    /// Sets the number of cells (m).
+    pub fn cells(mut self, cells: usize) -> Self {
+        self.cells = Some(cells);
+        self
+    }
+
+    /// Sets the number of bits in each cell (d), between 1 and 8.
+    pub fn cell_bits(mut self, cell_bits: u32) -> Self {
+        self.cell_bits = Some(cell_bits);
+        self
+    }
+
+    /// Sets the number of hash functions (K).
+    pub fn hashes(mut self, hashes: usize) -> Self {
+        self.hashes = Some(hashes);
+        self
+    }
+
+    /// Sets the number of cells decremented on each insert (P) explicitly.
+    pub fn decrements(mut self, decrements: usize) -> Self {
+        self.decrements = Some(decrements);
+        self
+    }
+
+    /// Sets the false-positive probability the filter settles at once it
+    /// is stable.
+    pub fn false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    /// Builds the arguments.
+    ///
+    /// When `false_positive_rate` is given, the number of decrements is
+    /// chosen so the stable false-positive rate meets it. An explicitly set
+    /// `decrements` takes precedence. Anything left unset falls back to
+    /// `StableBloomFilterArgs::default()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cells`, `hashes` or `decrements` is zero, `hashes`
+    /// exceeds `cells`, `cell_bits` is not between 1 and 8, or
+    /// `false_positive_rate` is not strictly between 0 and 1.
+    pub fn build(self) -> StableBloomFilterArgs {
+        let default = StableBloomFilterArgs::default();
+        let cells = self.cells.unwrap_or(default.cells);
+        let cell_bits = self.cell_bits.unwrap_or(default.cell_bits);
+        let hashes = self.hashes.unwrap_or(default.hashes);
+        assert!(cells > 0, "cells must be greater than zero");
+        assert!((1..=8).contains(&cell_bits), "cell_bits must be between 1 and 8");
+        assert!(hashes > 0, "hashes must be greater than zero");
+        assert!(hashes <= cells, "hashes must not exceed cells");
+
+        let optimal = self.false_positive_rate.map(|p| {
+            assert!(p > 0.0 && p < 1.0, "false_positive_rate must be between 0 and 1");
+            optimal_decrements(cells, cell_bits, hashes, p)
+        });
+        let decrements = self.decrements.or(optimal).unwrap_or(default.decrements);
+        assert!(decrements > 0, "decrements must be greater than zero");
+        StableBloomFilterArgs { cells, cell_bits, hashes, decrements }
+    }
+}
+
+/// The number of decrements P for which the stable false-positive rate is
+/// p: P = 1 / ((1 / (1 - p^(1/K))^(1/Max) - 1) (1/K - 1/m)).
+fn optimal_decrements(cells: usize, cell_bits: u32, hashes: usize, p: f64) -> usize {
+    let max = ((1u64 << cell_bits) - 1) as f64;
+    let zeros = (1.0 - p.powf(1.0 / hashes as f64)).powf(1.0 / max);
+    let denominator = (1.0 / zeros - 1.0) * (1.0 / hashes as f64 - 1.0 / cells as f64);
+    (1.0 / denominator).round().clamp(1.0, cells as f64) as usize
+}
+
+/// A stable bloom filter (Deng & Rafiei, "Approximately Detecting Duplicates
+/// for Streaming Data using Stable Bloom Filters"). Each insert first
+/// decrements P cells and then sets the K cells of the value to their
+/// maximum, so the fraction of zero cells converges to a fixed point
+/// instead of going to zero, however long the stream.
+///
+/// The price is false negatives: a value is forgotten once enough later
+/// inserts decrement one of its cells to zero.
+pub struct StableBloomFilter<T, E = Bytes, S = Xxh3> {
+    cells: Vec<u64>,
+    args: StableBloomFilterArgs,
+    hasher: S,
+    rng: SplitMix64,
+    _phantom: PhantomData<(T, E)>,
+}
+
+impl<T: AsRef<[u8]>> StableBloomFilter<T> {
+    /// Creates a new StableBloomFilter with the default arguments.
+    pub fn new() -> Self {
+        StableBloomFilter::with(StableBloomFilterArgs::default())
+    }
+
+    /// Creates a new StableBloomFilter with the given arguments.
+    pub fn with(args: StableBloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T: AsRef<[u8]>> Default for StableBloomFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash> StableBloomFilter<T, Hashed> {
+    /// Creates a new StableBloomFilter over `Hash` values with the default
+    /// arguments.
+    pub fn new_hashed() -> Self {
+        StableBloomFilter::with_hashed(StableBloomFilterArgs::default())
+    }
+
+    /// Creates a new StableBloomFilter over `Hash` values with the given
+    /// arguments.
+    pub fn with_hashed(args: StableBloomFilterArgs) -> Self {
+        Self::with_hasher(args, Xxh3::default())
+    }
+}
+
+impl<T, E, S: BloomHasher> StableBloomFilter<T, E, S> {
+    /// Creates a new StableBloomFilter with the given arguments and hash
+    /// strategy.
+    pub fn with_hasher(args: StableBloomFilterArgs, hasher: S) -> Self {
+        let per_word = 64 / args.cell_bits as usize;
+        Self {
+            cells: vec![0; args.cells.div_ceil(per_word)],
+            args,
+            hasher,
+            rng: SplitMix64::new(0x7374_6162_6c65),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The arguments of the StableBloomFilter.
+    pub fn args(&self) -> &StableBloomFilterArgs {
+        &self.args
+    }
+
+    /// The fraction of cells that are zero right now.
+    pub fn zero_ratio(&self) -> f64 {
+        let zeros = (0..self.args.cells).filter(|&idx| self.cell(idx) == 0).count();
+        zeros as f64 / self.args.cells as f64
+    }
+
+    /// The fraction of zero cells the filter converges to,
+    /// (1 / (1 + 1 / (P (1/K - 1/m))))^Max.
+    pub fn stable_point(&self) -> f64 {
+        let (cells, hashes) = (self.args.cells as f64, self.args.hashes as f64);
+        let decay = self.args.decrements as f64 * (1.0 / hashes - 1.0 / cells);
+        (1.0 / (1.0 + 1.0 / decay)).powi(self.args.max() as i32)
+    }
+
+    /// The false-positive probability once the filter is stable,
+    /// (1 - stable point)^K.
+    pub fn stable_false_positive_rate(&self) -> f64 {
+        (1.0 - self.stable_point()).powi(self.args.hashes as i32)
+    }
+
+    /// An upper bound on the probability that a value is reported absent
+    /// after `gap` other inserts since it was last inserted.
+    ///
+    /// Each insert decrements a given cell with probability P/m, and the
+    /// value is forgotten only once one of its K cells took Max decrements.
+    /// Later inserts setting the same cells again only help, so ignoring
+    /// them gives the bound K Pr[Binomial(gap, P/m) >= Max].
+    pub fn false_negative_bound(&self, gap: usize) -> f64 {
+        let max = self.args.max() as usize;
+        if gap < max {
+            return 0.0;
+        }
+        let p = (self.args.decrements as f64 / self.args.cells as f64).min(1.0);
+        if p == 1.0 {
+            return 1.0;
+        }
+
+        // Pr[X < Max], summing the binomial terms from Pr[X = 0].
+        let (n, ratio) = (gap as f64, p / (1.0 - p));
+        let mut term = (n * (-p).ln_1p()).exp();
+        let mut below = 0.0;
+        for i in 0..max {
+            below += term;
+            term *= (n - i as f64) / (i as f64 + 1.0) * ratio;
+        }
+        (self.args.hashes as f64 * (1.0 - below)).clamp(0.0, 1.0)
+    }
+
+    /// Inserts a new value into the StableBloomFilter: decrements P cells
+    /// starting at a random one, then sets the value's cells to Max.
+    pub fn insert<Q>(&mut self, value: &Q)
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        let start = self.rng.below(self.args.cells);
+        for offset in 0..self.args.decrements {
+            let idx = (start + offset) % self.args.cells;
+            let count = self.cell(idx);
+            if count > 0 {
+                self.set_cell(idx, count - 1);
+            }
+        }
+        for idx in self.calculate_hash_indices(value) {
+            self.set_cell(idx, self.args.max());
+        }
+    }
+
+    /// Checks if the StableBloomFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        if self.calculate_hash_indices(value).all(|idx| self.cell(idx) > 0) {
+            BloomFilterContainsResponse::Maybe
+        } else {
+            BloomFilterContainsResponse::No
+        }
+    }
+
+    fn cell(&self, idx: usize) -> u64 {
+        let (word, shift) = self.position(idx);
+        (self.cells[word] >> shift) & self.args.max()
+    }
+
+    fn set_cell(&mut self, idx: usize, count: u64) {
+        let (word, shift) = self.position(idx);
+        let mask = self.args.max() << shift;
+        self.cells[word] = (self.cells[word] & !mask) | (count << shift);
+    }
+
+    fn position(&self, idx: usize) -> (usize, usize) {
+        let bits = self.args.cell_bits as usize;
+        let per_word = 64 / bits;
+        (idx / per_word, (idx % per_word) * bits)
+    }
+
+    fn calculate_hash_indices<Q>(&self, value: &Q) -> HashIndices
+    where
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        HashIndices::new(E::hash_pair(value, &self.hasher), self.args.hashes, self.args.cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StableBloomFilter, StableBloomFilterArgs};
+    use crate::{BloomFilterContainsResponse, Hashed};
+
+    #[test]
+    fn stable_bloom_filter_remembers_recent_values() {
+        let mut stable: StableBloomFilter<String> = StableBloomFilter::new();
+        stable.insert("seen");
+        assert_eq!(stable.contains("seen"), BloomFilterContainsResponse::Maybe);
+        assert_eq!(stable.contains("unseen"), BloomFilterContainsResponse::No);
+    }
+
+    #[test]
+    fn stable_bloom_filter_settles_at_stable_point() {
+        let mut stable: StableBloomFilter<u64, Hashed> = StableBloomFilter::new_hashed();
+        for i in 0..200_000 {
+            stable.insert(&i);
+        }
+        assert!(
+            (stable.zero_ratio() - stable.stable_point()).abs() < 0.03,
+            "{} zeros, expected {}",
+            stable.zero_ratio(),
+            stable.stable_point()
+        );
+
+        // A plain BloomFilter of this size would answer "maybe" to nearly
+        // everything by now.
+        assert!((stable.stable_false_positive_rate() - 0.01).abs() < 0.002);
+        let false_positives = (1_000_000..1_100_000)
+            .filter(|i| stable.contains(i) == BloomFilterContainsResponse::Maybe)
+            .count();
+        assert!(false_positives < 2_000, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn stable_bloom_filter_bounds_false_negatives() {
+        let args = StableBloomFilterArgs::builder().cells(4096).decrements(40).build();
+        let mut stable: StableBloomFilter<u64, Hashed> = StableBloomFilter::with_hashed(args);
+        assert_eq!(stable.false_negative_bound(0), 0.0);
+        assert!(stable.false_negative_bound(100) < stable.false_negative_bound(1_000));
+        assert_eq!(stable.false_negative_bound(1_000_000), 1.0);
+
+        // Values inserted 300 inserts ago, well below the bound.
+        let gap = 300;
+        for i in 0..10_000 {
+            stable.insert(&i);
+        }
+        let forgotten = (10_000 - gap..10_000)
+            .filter(|i| stable.contains(i) == BloomFilterContainsResponse::No)
+            .count();
+        let bound = stable.false_negative_bound(gap as usize);
+        assert!(bound < 1.0);
+        assert!(
+            (forgotten as f64 / gap as f64) <= bound,
+            "{} forgotten, bound {}",
+            forgotten,
+            bound
+        );
+    }
+
+    #[test]
+    fn stable_bloom_filter_args_from_rate() {
+        let args = StableBloomFilterArgs::builder()
+            .cells(10_000)
+            .cell_bits(1)
+            .hashes(2)
+            .false_positive_rate(0.05)
+            .build();
+        let stable: StableBloomFilter<String> = StableBloomFilter::with(args);
+        assert!((stable.stable_false_positive_rate() - 0.05).abs() < 0.005);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    hash::mix64, rng::SplitMix64, BloomFilterContainsResponse, BloomHasher, Bytes, Hashed,
+    KeyEncoding, Xxh3,
+};
+use std::{borrow::Borrow, fmt, hash::Hash, marker::PhantomData, ops::BitXor};
+
+/// How many seeds construction tries before giving up.
+const MAX_ATTEMPTS: usize = 100;
+
+/// The error returned when a static filter cannot be built from its keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConstructionError {
+    /// Two keys hash to the same value, usually because the same key was
+    /// given twice, so no assignment of fingerprints exists.
+    DuplicateKeys,
+    /// No seed led to a complete peeling of the keys.
+    Unpeelable,
+}
+
+impl fmt::Display for ConstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstructionError::DuplicateKeys => write!(f, "duplicate keys prevent construction"),
+            ConstructionError::Unpeelable => {
+                write!(f, "no construction found after {} attempts", MAX_ATTEMPTS)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConstructionError {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+}
+
+/// The fingerprint stored per slot by a static filter: `u8` gives a
+/// false-positive rate of about 1/256, `u16` about 1/65536.
+pub trait Fingerprint:
+    Copy + Default + Eq + BitXor<Output = Self> + private::Sealed + 'static
+{
+    /// Derives the fingerprint of a key from its hash.
+    fn from_hash(hash: u64) -> Self;
+}
+
+impl Fingerprint for u8 {
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u8
+    }
+}
+
+impl Fingerprint for u16 {
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u16
+    }
+}
+
+/// Maps a key hash to the three slots holding its fingerprint.
+trait Layout {
+    fn slots(&self) -> usize;
+
+    fn positions(&self, hash: u64) -> [usize; 3];
+}
+
+/// The three equal blocks of a xor filter.
+#[derive(Clone, Debug)]
+struct XorLayout {
+    block_length: usize,
+}
+
+impl XorLayout {
+    fn new(keys: usize) -> Self {
+        let slots = 32 + (1.23 * keys as f64).ceil() as usize;
+        Self { block_length: slots.div_ceil(3) }
+    }
+}
+
+impl Layout for XorLayout {
+    fn slots(&self) -> usize {
+        3 * self.block_length
+    }
+
+    fn positions(&self, hash: u64) -> [usize; 3] {
+        let reduce = |hash: u64| ((hash as u32 as u64 * self.block_length as u64) >> 32) as usize;
+        [
+            reduce(hash),
+            self.block_length + reduce(hash.rotate_left(21)),
+            2 * self.block_length + reduce(hash.rotate_left(42)),
+        ]
+    }
+}
+
+/// Three consecutive segments out of many, as in a binary fuse filter.
+#[derive(Clone, Debug)]
+struct FuseLayout {
+    segment_length: usize,
+    segment_count: usize,
+}
+
+impl FuseLayout {
+    fn new(keys: usize) -> Self {
+        let segment_length = if keys == 0 {
+            4
+        } else {
+            let exponent = ((keys as f64).ln() / 3.33f64.ln() + 2.25).floor() as u32;
+            1usize << exponent.min(18)
+        };
+        let capacity = if keys <= 1 {
+            0
+        } else {
+            let factor = f64::max(1.125, 0.875 + 0.25 * 1e6f64.ln() / (keys as f64).ln());
+            (keys as f64 * factor).round() as usize
+        };
+        let segment_count = capacity.div_ceil(segment_length).saturating_sub(2).max(1);
+        Self { segment_length, segment_count }
+    }
+}
+
+impl Layout for FuseLayout {
+    fn slots(&self) -> usize {
+        (self.segment_count + 2) * self.segment_length
+    }
+
+    fn positions(&self, hash: u64) -> [usize; 3] {
+        let mask = self.segment_length as u64 - 1;
+        let span = (self.segment_count * self.segment_length) as u128;
+        let h0 = ((hash as u128 * span) >> 64) as usize;
+        let h1 = (h0 + self.segment_length) ^ ((hash >> 18) & mask) as usize;
+        let h2 = (h0 + 2 * self.segment_length) ^ (hash & mask) as usize;
+        [h0, h1, h2]
+    }
+}
+
+/// The seeded 64-bit hash a static filter works with.
+fn key_hash((h1, h2): (u64, u64), seed: u64) -> u64 {
+    mix64(h1 ^ h2.rotate_left(32) ^ seed)
+}
+
+/// Finds a seed for which every key can be peeled off the slots and
+/// assigns the fingerprints.
+fn build<F: Fingerprint, L: Layout>(
+    pairs: &[(u64, u64)],
+    layout: &L,
+) -> Result<(u64, Box<[F]>), ConstructionError> {
+    let mut unique: Vec<u64> = pairs.iter().map(|&pair| key_hash(pair, 0)).collect();
+    unique.sort_unstable();
+    if unique.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(ConstructionError::DuplicateKeys);
+    }
+
+    let mut rng = SplitMix64::new(0x7374_6174_6963);
+    let mut counts = vec![0u32; layout.slots()];
+    let mut xors = vec![0u64; layout.slots()];
+    let mut stack = Vec::with_capacity(pairs.len());
+    let mut queue = Vec::new();
+    for _ in 0..MAX_ATTEMPTS {
+        let seed = rng.next_u64();
+        counts.fill(0);
+        xors.fill(0);
+        stack.clear();
+        for &pair in pairs {
+            let hash = key_hash(pair, seed);
+            for slot in layout.positions(hash) {
+                counts[slot] += 1;
+                xors[slot] ^= hash;
+            }
+        }
+
+        queue.extend((0..counts.len()).filter(|&slot| counts[slot] == 1));
+        while let Some(slot) = queue.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+            let hash = xors[slot];
+            stack.push((hash, slot));
+            for other in layout.positions(hash) {
+                counts[other] -= 1;
+                xors[other] ^= hash;
+                if counts[other] == 1 {
+                    queue.push(other);
+                }
+            }
+        }
+
+        if stack.len() == pairs.len() {
+            let mut fingerprints = vec![F::default(); layout.slots()].into_boxed_slice();
+            for &(hash, slot) in stack.iter().rev() {
+                // The slot itself is still unassigned, so it adds nothing.
+                let [a, b, c] = layout.positions(hash);
+                fingerprints[slot] =
+                    F::from_hash(hash) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+            }
+            return Ok((seed, fingerprints));
+        }
+    }
+    Err(ConstructionError::Unpeelable)
+}
+
+/// An immutable xor filter (Graf & Lemire, "Xor Filters: Faster and
+/// Smaller Than Bloom and Cuckoo Filters") built from a fixed set of keys.
+/// Uses about 1.23 fingerprints per key.
+pub struct XorFilter<T, F, E = Bytes, S = Xxh3> {
+    fingerprints: Box<[F]>,
+    layout: XorLayout,
+    seed: u64,
+    len: usize,
+    hasher: S,
+    _phantom: PhantomData<fn() -> (T, E)>,
+}
+
+/// A xor filter with 8-bit fingerprints.
+pub type XorFilter8<T, E = Bytes, S = Xxh3> = XorFilter<T, u8, E, S>;
+
+/// A xor filter with 16-bit fingerprints.
+pub type XorFilter16<T, E = Bytes, S = Xxh3> = XorFilter<T, u16, E, S>;
+
+impl<T: AsRef<[u8]>, F: Fingerprint> XorFilter<T, F> {
+    /// Builds a XorFilter from the given keys.
+    pub fn new(keys: &[T]) -> Result<Self, ConstructionError> {
+        Self::with_hasher(keys, Xxh3::default())
+    }
+}
+
+impl<T: Hash, F: Fingerprint> XorFilter<T, F, Hashed> {
+    /// Builds a XorFilter from the given `Hash` keys.
+    pub fn new_hashed(keys: &[T]) -> Result<Self, ConstructionError> {
+        Self::with_hasher(keys, Xxh3::default())
+    }
+}
+
+impl<T, F: Fingerprint, E, S: BloomHasher> XorFilter<T, F, E, S> {
+    /// Builds a XorFilter from the given keys and hash strategy.
+    pub fn with_hasher(keys: &[T], hasher: S) -> Result<Self, ConstructionError>
+    where
+        E: KeyEncoding<T>,
+    {
+        let pairs: Vec<_> = keys.iter().map(|key| E::hash_pair(key, &hasher)).collect();
+        let layout = XorLayout::new(keys.len());
+        let (seed, fingerprints) = build(&pairs, &layout)?;
+        Ok(Self { fingerprints, layout, seed, len: keys.len(), hasher, _phantom: PhantomData })
+    }
+
+    /// The hash strategy of the XorFilter.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// The number of keys the XorFilter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the XorFilter was built from no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bits of fingerprint stored per key.
+    pub fn bits_per_key(&self) -> f64 {
+        (self.fingerprints.len() * 8 * std::mem::size_of::<F>()) as f64 / self.len.max(1) as f64
+    }
+
+    /// Checks if the XorFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        contains(&self.fingerprints, &self.layout, self.seed, E::hash_pair(value, &self.hasher))
+    }
+}
+
+/// An immutable binary fuse filter (Graf & Lemire, "Binary Fuse Filters:
+/// Fast and Smaller Than Xor Filters") built from a fixed set of keys.
+/// Uses about 1.125 fingerprints per key for large key sets.
+pub struct BinaryFuseFilter<T, F, E = Bytes, S = Xxh3> {
+    fingerprints: Box<[F]>,
+    layout: FuseLayout,
+    seed: u64,
+    len: usize,
+    hasher: S,
+    _phantom: PhantomData<fn() -> (T, E)>,
+}
+
+/// A binary fuse filter with 8-bit fingerprints.
+pub type BinaryFuseFilter8<T, E = Bytes, S = Xxh3> = BinaryFuseFilter<T, u8, E, S>;
+
+/// A binary fuse filter with 16-bit fingerprints.
+pub type BinaryFuseFilter16<T, E = Bytes, S = Xxh3> = BinaryFuseFilter<T, u16, E, S>;
+
+impl<T: AsRef<[u8]>, F: Fingerprint> BinaryFuseFilter<T, F> {
+    /// Builds a BinaryFuseFilter from the given keys.
+    pub fn new(keys: &[T]) -> Result<Self, ConstructionError> {
+        Self::with_hasher(keys, Xxh3::default())
+    }
+}
+
+impl<T: Hash, F: Fingerprint> BinaryFuseFilter<T, F, Hashed> {
+    /// Builds a BinaryFuseFilter from the given `Hash` keys.
+    pub fn new_hashed(keys: &[T]) -> Result<Self, ConstructionError> {
+        Self::with_hasher(keys, Xxh3::default())
+    }
+}
+
+impl<T, F: Fingerprint, E, S: BloomHasher> BinaryFuseFilter<T, F, E, S> {
+    /// Builds a BinaryFuseFilter from the given keys and hash strategy.
+    pub fn with_hasher(keys: &[T], hasher: S) -> Result<Self, ConstructionError>
+    where
+        E: KeyEncoding<T>,
+    {
+        let pairs: Vec<_> = keys.iter().map(|key| E::hash_pair(key, &hasher)).collect();
+        let layout = FuseLayout::new(keys.len());
+        let (seed, fingerprints) = build(&pairs, &layout)?;
+        Ok(Self { fingerprints, layout, seed, len: keys.len(), hasher, _phantom: PhantomData })
+    }
+
+    /// The hash strategy of the BinaryFuseFilter.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// The number of keys the BinaryFuseFilter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the BinaryFuseFilter was built from no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bits of fingerprint stored per key.
+    pub fn bits_per_key(&self) -> f64 {
+        (self.fingerprints.len() * 8 * std::mem::size_of::<F>()) as f64 / self.len.max(1) as f64
+    }
+
+    /// Checks if the BinaryFuseFilter contains the given value.
+    pub fn contains<Q>(&self, value: &Q) -> BloomFilterContainsResponse
+    where
+        T: Borrow<Q>,
+        E: KeyEncoding<Q>,
+        Q: ?Sized,
+    {
+        contains(&self.fingerprints, &self.layout, self.seed, E::hash_pair(value, &self.hasher))
+    }
+}
+
+fn contains<F: Fingerprint, L: Layout>(
+    fingerprints: &[F],
+    layout: &L,
+    seed: u64,
+    pair: (u64, u64),
+) -> BloomFilterContainsResponse {
+    let hash = key_hash(pair, seed);
+    let [a, b, c] = layout.positions(hash);
+    if fingerprints[a] ^ fingerprints[b] ^ fingerprints[c] == F::from_hash(hash) {
+        BloomFilterContainsResponse::Maybe
+    } else {
+        BloomFilterContainsResponse::No
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BinaryFuseFilter16, BinaryFuseFilter8, ConstructionError, XorFilter16, XorFilter8,
+    };
+    use crate::BloomFilterContainsResponse;
+
+    fn false_positives(contains: impl Fn(&u64) -> BloomFilterContainsResponse) -> usize {
+        (1_000_000..1_100_000u64)
+            .filter(|i| contains(i) == BloomFilterContainsResponse::Maybe)
+            .count()
+    }
+
+    #[test]
+    fn xor_filter_has_no_false_negatives() {
+        let keys: Vec<u64> = (0..100_000).collect();
+        let filter = XorFilter8::new_hashed(&keys).unwrap();
+        assert_eq!(filter.len(), 100_000);
+        assert!(keys.iter().all(|key| filter.contains(key) == BloomFilterContainsResponse::Maybe));
+        // About 1/256 of 100k.
+        assert!(false_positives(|i| filter.contains(i)) < 600);
+        assert!(filter.bits_per_key() < 10.0);
+
+        let filter = XorFilter16::new_hashed(&keys).unwrap();
+        assert!(keys.iter().all(|key| filter.contains(key) == BloomFilterContainsResponse::Maybe));
+        assert!(false_positives(|i| filter.contains(i)) < 10);
+    }
+
+    #[test]
+    fn binary_fuse_filter_has_no_false_negatives() {
+        let keys: Vec<u64> = (0..100_000).collect();
+        let filter = BinaryFuseFilter8::new_hashed(&keys).unwrap();
+        assert!(keys.iter().all(|key| filter.contains(key) == BloomFilterContainsResponse::Maybe));
+        assert!(false_positives(|i| filter.contains(i)) < 600);
+        assert!(filter.bits_per_key() < 9.8);
+
+        let filter = BinaryFuseFilter16::new_hashed(&keys).unwrap();
+        assert!(keys.iter().all(|key| filter.contains(key) == BloomFilterContainsResponse::Maybe));
+        assert!(false_positives(|i| filter.contains(i)) < 10);
+    }
+
+    #[test]
+    fn static_filters_handle_small_key_sets() {
+        for n in 0..50 {
+            let keys: Vec<String> = (0..n).map(|i| format!("key{}", i)).collect();
+            let xor = XorFilter8::new(&keys).unwrap();
+            let fuse = BinaryFuseFilter8::new(&keys).unwrap();
+            for key in &keys {
+                assert_eq!(xor.contains(key.as_str()), BloomFilterContainsResponse::Maybe);
+                assert_eq!(fuse.contains(key.as_str()), BloomFilterContainsResponse::Maybe);
+            }
+        }
+    }
+
+    #[test]
+    fn static_filters_reject_duplicate_keys() {
+        let keys = ["a", "b", "a"];
+        assert_eq!(XorFilter8::new(&keys).err(), Some(ConstructionError::DuplicateKeys));
+        assert_eq!(BinaryFuseFilter16::new(&keys).err(), Some(ConstructionError::DuplicateKeys));
+    }
+}